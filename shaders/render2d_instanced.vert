@@ -0,0 +1,46 @@
+#version 450
+
+layout(location=0) in vec2 a_x_axis;
+layout(location=1) in vec2 a_y_axis;
+layout(location=2) in vec2 a_translation;
+layout(location=3) in vec2 a_uv_offset;
+layout(location=4) in vec2 a_uv_scale;
+layout(location=5) in vec4 a_color;
+layout(location=6) in uint a_tex_index;
+layout(location=7) in uint a_flags;
+layout(location=8) in uint a_normal_index;
+
+layout(set=1, binding=0) 
+uniform Uniforms {
+    mat4 projection;
+};
+
+layout(location=0) out vec4 v_color;
+layout(location=1) out vec2 v_texture_coord;
+layout(location=2) out uint v_tex_index;
+layout(location=3) out vec2 v_world_position;
+layout(location=4) out uint v_flags;
+layout(location=5) out uint v_normal_index;
+layout(location=6) out vec2 v_sdf;
+
+// Same corner order as the sprite quads: top right, top left, bottom left, bottom right
+const vec2 corners[4] = vec2[4](
+    vec2(1.0, 0.0),
+    vec2(0.0, 0.0),
+    vec2(0.0, 1.0),
+    vec2(1.0, 1.0)
+);
+
+void main() {
+    vec2 corner = corners[gl_VertexIndex % 4];
+    vec2 position = a_x_axis * corner.x + a_y_axis * corner.y + a_translation;
+
+    v_color = a_color;
+    v_texture_coord = a_uv_offset + corner * a_uv_scale;
+    v_tex_index = a_tex_index;
+    v_world_position = position;
+    v_flags = a_flags;
+    v_normal_index = a_normal_index;
+    v_sdf = vec2(0.5, 0.0);
+    gl_Position = projection * vec4(position, 0.0, 1.0);
+}
@@ -1,27 +1,178 @@
 #version 450
 
+#define MAX_LIGHTS 32
+#define MAX_OCCLUDERS 64
+#define SHADOW_SAMPLES 5
+#define FLAG_LIT 1u
+#define FLAG_WRAP 2u
+#define FLAG_SINGLE_CHANNEL 4u
+#define FLAG_SDF 8u
+#define NO_NORMAL_MAP 0xFFFFFFFFu
+
 layout(location=0) in vec4 v_color;
 layout(location=1) in vec2 v_texture_coord;
 layout(location=2) flat in uint v_tex_index;
+layout(location=3) in vec2 v_world_position;
+layout(location=4) flat in uint v_flags;
+layout(location=5) flat in uint v_normal_index;
+// Threshold and softness of distance field glyphs
+layout(location=6) flat in vec2 v_sdf;
 
 layout(location=0) out vec4 o_color;
 
-layout(set = 0, binding = 0) uniform sampler texture_sampler;
 layout(set = 0, binding = 1) uniform texture2D texture_1;
 layout(set = 0, binding = 2) uniform texture2D texture_2;
-// layout(set = 0, binding = 3) uniform texture2D texture_3;
+layout(set = 0, binding = 3) uniform texture2D texture_3;
+layout(set = 0, binding = 4) uniform texture2D texture_4;
+layout(set = 0, binding = 5) uniform sampler sampler_1;
+layout(set = 0, binding = 6) uniform sampler sampler_2;
+layout(set = 0, binding = 7) uniform sampler sampler_3;
+layout(set = 0, binding = 8) uniform sampler sampler_4;
+layout(set = 0, binding = 9) uniform sampler wrap_sampler_1;
+layout(set = 0, binding = 10) uniform sampler wrap_sampler_2;
+layout(set = 0, binding = 11) uniform sampler wrap_sampler_3;
+layout(set = 0, binding = 12) uniform sampler wrap_sampler_4;
+
+struct Light {
+    vec4 position;  // xy position, z radius, w intensity
+    vec4 color;     // rgb color, w height
+    vec4 direction; // xy direction, z cos inner angle, w cos outer angle
+    vec4 params;    // x is spot, y shadow softness
+};
+
+layout(set = 2, binding = 0)
+uniform Lighting {
+    vec4 ambient;   // rgb ambient, w enabled
+    uvec4 counts;   // x lights, y occluders
+    Light lights[MAX_LIGHTS];
+    vec4 occluders[MAX_OCCLUDERS];
+};
+
+vec4 sample_slot(texture2D slot, sampler slot_sampler, sampler wrap_sampler, vec2 uv, vec2 duvdx, vec2 duvdy) {
+    if ((v_flags & FLAG_WRAP) != 0u) {
+        return textureGrad(sampler2D(slot, wrap_sampler), uv, duvdx, duvdy);
+    }
+    return textureGrad(sampler2D(slot, slot_sampler), uv, duvdx, duvdy);
+}
+
+vec4 sample_texture(uint index, vec2 uv, vec2 duvdx, vec2 duvdy) {
+	switch(index)
+	{
+        case 0: return sample_slot(texture_1, sampler_1, wrap_sampler_1, uv, duvdx, duvdy);
+        case 1: return sample_slot(texture_2, sampler_2, wrap_sampler_2, uv, duvdx, duvdy);
+        case 2: return sample_slot(texture_3, sampler_3, wrap_sampler_3, uv, duvdx, duvdy);
+        case 3: return sample_slot(texture_4, sampler_4, wrap_sampler_4, uv, duvdx, duvdy);
+	}
+    return vec4(1.0);
+}
+
+bool segment_hits_rect(vec2 from, vec2 to, vec4 rect) {
+    vec2 rect_min = rect.xy;
+    vec2 rect_max = rect.xy + rect.zw;
+
+    // The occluder surface itself is not shadowed
+    if (all(greaterThanEqual(from, rect_min)) && all(lessThanEqual(from, rect_max))) {
+        return false;
+    }
+
+    vec2 inv_direction = 1.0 / (to - from);
+    vec2 t0 = (rect_min - from) * inv_direction;
+    vec2 t1 = (rect_max - from) * inv_direction;
+    vec2 t_min = min(t0, t1);
+    vec2 t_max = max(t0, t1);
+    float near = max(t_min.x, t_min.y);
+    float far = min(t_max.x, t_max.y);
+
+    return near <= far && far >= 0.0 && near <= 1.0;
+}
+
+bool is_visible(vec2 from, vec2 to) {
+    for (uint i = 0u; i < counts.y; i++) {
+        if (segment_hits_rect(from, to, occluders[i])) {
+            return false;
+        }
+    }
+    return true;
+}
+
+float cast_shadow(vec2 position, vec2 light_position, float softness) {
+    if (softness <= 0.0) {
+        return is_visible(position, light_position) ? 1.0 : 0.0;
+    }
+
+    // Sample across the light source to get a penumbra
+    vec2 direction = normalize(light_position - position);
+    vec2 side = vec2(-direction.y, direction.x) * softness;
+    float lit = 0.0;
+    for (int i = 0; i < SHADOW_SAMPLES; i++) {
+        float offset = float(i) / float(SHADOW_SAMPLES - 1) * 2.0 - 1.0;
+        lit += is_visible(position, light_position + side * offset) ? 1.0 : 0.0;
+    }
+    return lit / float(SHADOW_SAMPLES);
+}
+
+vec3 light_fragment(vec2 duvdx, vec2 duvdy) {
+    bool has_normal = v_normal_index != NO_NORMAL_MAP;
+    vec3 normal = vec3(0.0, 0.0, 1.0);
+    if (has_normal) {
+        normal = sample_texture(v_normal_index, v_texture_coord, duvdx, duvdy).rgb * 2.0 - 1.0;
+        // Normal maps are y up, the world is y down
+        normal = normalize(vec3(normal.x, -normal.y, normal.z));
+    }
+
+    vec3 light_sum = ambient.rgb;
+    for (uint i = 0u; i < counts.x; i++) {
+        Light light = lights[i];
+        vec2 to_light = light.position.xy - v_world_position;
+        float distance = length(to_light);
+        float radius = light.position.z;
+        if (distance >= radius) {
+            continue;
+        }
+
+        float attenuation = 1.0 - distance / radius;
+        attenuation *= attenuation;
+
+        if (light.params.x > 0.5 && distance > 0.0) {
+            float angle = dot(-to_light / distance, light.direction.xy);
+            attenuation *= smoothstep(light.direction.w, light.direction.z, angle);
+        }
+
+        float diffuse = 1.0;
+        if (has_normal) {
+            diffuse = max(dot(normal, normalize(vec3(to_light, light.color.w))), 0.0);
+        }
+
+        if (attenuation * diffuse <= 0.0) {
+            continue;
+        }
+
+        float shadow = cast_shadow(v_world_position, light.position.xy, light.params.y);
+        light_sum += light.color.rgb * light.position.w * attenuation * diffuse * shadow;
+    }
+
+    return light_sum;
+}
 
 void main() {
     vec2 duvdx = dFdx(v_texture_coord);
     vec2 duvdy = dFdy(v_texture_coord);
 
-    vec4 texColor = v_color;    
-	switch(v_tex_index)
-	{
-        case 0: texColor *= textureGrad(sampler2D(texture_1, texture_sampler), v_texture_coord, duvdx, duvdy); break;
-        case 1: texColor *= textureGrad(sampler2D(texture_2, texture_sampler), v_texture_coord, duvdx, duvdy); break;
-        // case 2: texColor *= textureGrad(sampler2D(texture_3, texture_sampler), v_texture_coord, duvdx, duvdy); break;
-	}
+    vec4 sampled = sample_texture(v_tex_index, v_texture_coord, duvdx, duvdy);
+    if ((v_flags & FLAG_SDF) != 0u) {
+        // Antialiased over about a screen pixel whatever the scale of the glyph
+        float distance = sampled.r;
+        float width = fwidth(distance) * 0.5;
+        float alpha = smoothstep(v_sdf.x - width - v_sdf.y, v_sdf.x + width, distance);
+        sampled = vec4(1.0, 1.0, 1.0, alpha);
+    } else if ((v_flags & FLAG_SINGLE_CHANNEL) != 0u) {
+        sampled = vec4(1.0, 1.0, 1.0, sampled.r);
+    }
+    vec4 texColor = v_color * sampled;
+
+    if (ambient.w > 0.5 && (v_flags & FLAG_LIT) != 0u) {
+        texColor.rgb *= light_fragment(duvdx, duvdy);
+    }
     
     o_color = texColor;
 }
\ No newline at end of file
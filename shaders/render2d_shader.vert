@@ -4,6 +4,9 @@ layout(location=0) in vec3 a_position;
 layout(location=1) in vec4 a_color;
 layout(location=2) in vec2 a_texture_coord;
 layout(location=3) in uint a_tex_index;
+layout(location=4) in uint a_flags;
+layout(location=5) in uint a_normal_index;
+layout(location=6) in vec2 a_sdf;
 
 layout(set=1, binding=0) 
 uniform Uniforms {
@@ -13,10 +16,18 @@ uniform Uniforms {
 layout(location=0) out vec4 v_color;
 layout(location=1) out vec2 v_texture_coord;
 layout(location=2) out uint v_tex_index;
+layout(location=3) out vec2 v_world_position;
+layout(location=4) out uint v_flags;
+layout(location=5) out uint v_normal_index;
+layout(location=6) out vec2 v_sdf;
 
 void main() {
     v_color = a_color;
     v_texture_coord = a_texture_coord;
     v_tex_index = a_tex_index;
+    v_world_position = a_position.xy;
+    v_flags = a_flags;
+    v_normal_index = a_normal_index;
+    v_sdf = a_sdf;
     gl_Position = projection * vec4(a_position, 1.0);
 }
\ No newline at end of file
@@ -2,14 +2,22 @@ use image::DynamicImage;
 
 use crate::{
     audio::{Audio, AudioSettings},
+    core::{
+        components::{ParticleEmitterSettings, SubTexture},
+        localization::{Argument, Localization},
+        sprite_sheet::{SpriteSheet, SpriteSheetBuilder},
+        texture_atlas::{TextureAtlas, TextureAtlasBuilder},
+    },
     error::{BreakoutError, BreakoutResult},
-    font::Font,
+    font::{fallback_runs, Font},
     render::{renderer::Renderer, texture::Texture},
 };
 use std::{collections::HashMap, rc::Rc};
 
 use super::game_window::ReadOnlyRc;
 
+pub use crate::render::texture::{TextureAddressMode, TextureFilter, TextureOptions};
+
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
 pub struct TextureId(i32);
 
@@ -19,6 +27,9 @@ pub struct AudioId(i32);
 #[derive(Hash, PartialEq, Eq, Clone)]
 pub struct FontId(i32);
 
+#[derive(Hash, PartialEq, Eq, Clone)]
+pub struct ParticleEmitterId(i32);
+
 struct AutoIncrementId {
     ids: HashMap<String, i32>,
 }
@@ -48,6 +59,9 @@ pub struct AssetManager {
     textures: HashMap<TextureId, Rc<Texture>>,
     audios: HashMap<AudioId, Audio>,
     fonts: HashMap<FontId, Rc<Font>>,
+    font_fallbacks: HashMap<FontId, Vec<FontId>>,
+    particle_emitters: HashMap<ParticleEmitterId, ParticleEmitterSettings>,
+    localization: Localization,
     renderer: ReadOnlyRc<Renderer>,
 }
 
@@ -58,6 +72,9 @@ impl AssetManager {
             textures: HashMap::new(),
             audios: HashMap::new(),
             fonts: HashMap::new(),
+            font_fallbacks: HashMap::new(),
+            particle_emitters: HashMap::new(),
+            localization: Localization::default(),
             renderer,
         }
     }
@@ -65,15 +82,71 @@ impl AssetManager {
 
 impl AssetManager {
     pub fn load_texture(&mut self, path: &str) -> BreakoutResult<TextureId> {
+        self.load_texture_with_options(path, TextureOptions::default())
+    }
+
+    pub fn load_texture_with_options(
+        &mut self,
+        path: &str,
+        options: TextureOptions,
+    ) -> BreakoutResult<TextureId> {
         // let image = image::open(path).map_err(BreakoutError::ImageError)?;
-        let renderer = self.renderer.borrow();
-        let mut texture = Texture::from_file(path, renderer.device(), renderer.queue());
+        let texture = {
+            let renderer = self.renderer.borrow();
+            Texture::from_file_with_options(path, options, renderer.device(), renderer.queue())?
+        };
+
+        Ok(self.add_texture(texture))
+    }
+
+    // Normal maps are loaded without the sRGB conversion of the color textures
+    pub fn load_normal_map(&mut self, path: &str) -> BreakoutResult<TextureId> {
+        self.load_texture_with_options(path, TextureOptions::default().with_linear_color(true))
+    }
+
+    // Packs the images into as few textures as possible
+    pub fn build_atlas(&mut self, builder: TextureAtlasBuilder) -> BreakoutResult<TextureAtlas> {
+        let mut atlas = TextureAtlas {
+            pages: Vec::new(),
+            regions: HashMap::new(),
+        };
+
+        for page in builder.pack()? {
+            let (width, height) = page.image.dimensions();
+            let texture = {
+                let renderer = self.renderer.borrow();
+                Texture::from_dynamic_image_with_options(
+                    DynamicImage::ImageRgba8(page.image),
+                    builder.options,
+                    renderer.device(),
+                    renderer.queue(),
+                )?
+            };
+            let id = self.add_texture(texture);
+
+            for (name, region) in page.regions {
+                let sub_texture =
+                    SubTexture::new_with_texture_size(region, width as f32, height as f32);
+                atlas.regions.insert(name, (id.clone(), sub_texture));
+            }
+            atlas.pages.push(id);
+        }
+
+        Ok(atlas)
+    }
 
+    // Loads a TexturePacker or Aseprite JSON export together with its image
+    pub fn load_sprite_sheet(&mut self, path: &str) -> BreakoutResult<SpriteSheet> {
+        let (image_path, builder) = SpriteSheetBuilder::load(path)?;
+        let texture_id = self.load_texture(&image_path)?;
+        builder.build(texture_id)
+    }
+
+    fn add_texture(&mut self, mut texture: Texture) -> TextureId {
         let id = TextureId(self.auto_increment_id.get_id::<TextureId>());
         texture.id = Some(id.0);
         self.textures.insert(id.clone(), Rc::new(texture));
-
-        Ok(id)
+        id
     }
 
     pub fn get_texture(&self, id: &TextureId) -> &Rc<Texture> {
@@ -102,8 +175,30 @@ impl AssetManager {
 }
 
 impl AssetManager {
+    // TrueType and OpenType fonts are rasterized with FreeType, .fnt files are loaded as
+    // AngelCode BMFont bitmap fonts
     pub fn load_font(&mut self, path: &str) -> BreakoutResult<FontId> {
-        let font = Font::new(path)?;
+        let is_bitmap = std::path::Path::new(path)
+            .extension()
+            .map_or(false, |extension| extension.eq_ignore_ascii_case("fnt"));
+        let font = if is_bitmap {
+            let renderer = self.renderer.borrow();
+            Font::new_bitmap(path, |image| {
+                Texture::from_dynamic_image(image, renderer.device(), renderer.queue())
+            })?
+        } else {
+            Font::new(path)?
+        };
+
+        let id = FontId(self.auto_increment_id.get_id::<FontId>());
+        self.fonts.insert(id.clone(), Rc::new(font));
+
+        Ok(id)
+    }
+
+    // A single distance field atlas is used for every size of the font
+    pub fn load_sdf_font(&mut self, path: &str) -> BreakoutResult<FontId> {
+        let font = Font::new_sdf(path)?;
 
         let id = FontId(self.auto_increment_id.get_id::<FontId>());
         self.fonts.insert(id.clone(), Rc::new(font));
@@ -115,6 +210,42 @@ impl AssetManager {
         &self.fonts[id]
     }
 
+    // Characters missing from the font are drawn with the first fallback that has them, on
+    // the baseline of the font
+    pub fn set_font_fallbacks(&mut self, id: &FontId, fallbacks: Vec<FontId>) {
+        if fallbacks.is_empty() {
+            self.font_fallbacks.remove(id);
+        } else {
+            self.font_fallbacks.insert(id.clone(), fallbacks);
+        }
+    }
+
+    pub fn get_font_fallbacks(&self, id: &FontId) -> &[FontId] {
+        self.font_fallbacks
+            .get(id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    // Splits the text in runs of the font and of its fallbacks, None is the default font
+    pub(crate) fn font_runs(
+        &self,
+        id: Option<&FontId>,
+        text: &str,
+    ) -> Vec<(Option<FontId>, String)> {
+        let (id, fallbacks) = match id.and_then(|id| Some((id, self.font_fallbacks.get(id)?))) {
+            Some(fallbacks) if !text.is_empty() => fallbacks,
+            _ => return vec![(id.cloned(), text.to_string())],
+        };
+
+        let ids = std::iter::once(id).chain(fallbacks).collect::<Vec<_>>();
+        let fonts = ids.iter().map(|id| &*self.fonts[id]).collect::<Vec<_>>();
+        fallback_runs(text, &fonts)
+            .into_iter()
+            .map(|(font, run)| (Some(ids[font].clone()), run))
+            .collect()
+    }
+
     pub fn get_font_with_size<F>(
         &mut self,
         id: &FontId,
@@ -122,7 +253,7 @@ impl AssetManager {
         get_texture: F,
     ) -> BreakoutResult<&Font>
     where
-        F: FnOnce(DynamicImage) -> Texture,
+        F: FnMut(DynamicImage) -> BreakoutResult<Texture>,
     {
         if !self.fonts[&id].has_size(size) {
             // TODO unsafe
@@ -133,4 +264,74 @@ impl AssetManager {
 
         Ok(&self.fonts[id])
     }
+
+    // Makes sure every glyph of the text is cached before it's drawn
+    pub fn get_font_with_text<F>(
+        &mut self,
+        id: &FontId,
+        text: &str,
+        size: u32,
+        get_texture: F,
+    ) -> BreakoutResult<&Font>
+    where
+        F: FnMut(DynamicImage) -> BreakoutResult<Texture>,
+    {
+        if !self.fonts[id].has_text(text, size) {
+            let mut font = self.fonts.remove(id).unwrap();
+            let result = match Rc::get_mut(&mut font) {
+                Some(font) => font.cache_text(text, size, get_texture),
+                None => Err(BreakoutError::GenericError(
+                    "Glyphs can't be cached while the font is shared",
+                )),
+            };
+            self.fonts.insert(id.clone(), font);
+            result?;
+        }
+
+        Ok(&self.fonts[id])
+    }
+}
+
+impl AssetManager {
+    pub fn load_particle_emitter(&mut self, path: &str) -> BreakoutResult<ParticleEmitterId> {
+        let json_string = std::fs::read_to_string(path).map_err(BreakoutError::IOError)?;
+        let settings: ParticleEmitterSettings = serde_json::from_str(&json_string)
+            .map_err(|_| BreakoutError::GenericError("serde_json::from_str failed"))?;
+
+        let id = ParticleEmitterId(self.auto_increment_id.get_id::<ParticleEmitterId>());
+        self.particle_emitters.insert(id.clone(), settings);
+
+        Ok(id)
+    }
+
+    // Clone the settings into ParticleEmitter2D::new to spawn an emitter
+    pub fn get_particle_emitter(&self, id: &ParticleEmitterId) -> &ParticleEmitterSettings {
+        &self.particle_emitters[id]
+    }
+
+    // Adds the strings of a JSON or Fluent (.ftl) file to the table of the language, the
+    // first loaded language is the current one
+    pub fn load_strings(&mut self, language: &str, path: &str) -> BreakoutResult {
+        self.localization.load(language, path)
+    }
+
+    // Labels with a localization key are updated on the next frame, use
+    // GameContext::set_language from a scene
+    pub fn set_language(&mut self, language: &str) {
+        self.localization.set_language(language);
+    }
+
+    pub fn get_language(&self) -> Option<&str> {
+        self.localization.language()
+    }
+
+    // The string of the key in the current language with the {name} placeholders replaced,
+    // the key itself when it's missing
+    pub fn localize(&self, key: &str, args: &[(&str, Argument)]) -> String {
+        self.localization.localize(key, args)
+    }
+
+    pub(crate) fn get_localization(&self) -> &Localization {
+        &self.localization
+    }
 }
@@ -1,5 +1,8 @@
+use super::Transform2D;
 use crate::shapes::rectangle::Rect;
 
+pub const ALL_LAYERS: u32 = u32::MAX;
+
 pub enum ScaleMode {
     Keep,
     KeepWidth,
@@ -24,13 +27,84 @@ impl Default for AnchorMode {
     }
 }
 
-#[derive(Default)]
+pub struct CameraFollow {
+    pub target: hecs::Entity,
+    // How fast the camera catches up with the target, 0.0 snaps to it
+    pub smoothing: f32,
+    // Half size of the area around the view center where the target can move freely
+    pub deadzone: glam::Vec2,
+}
+
+impl CameraFollow {
+    pub fn new(target: hecs::Entity) -> CameraFollow {
+        CameraFollow {
+            target,
+            smoothing: 0.0,
+            deadzone: glam::Vec2::ZERO,
+        }
+    }
+}
+
+pub struct CameraShake {
+    pub max_offset: glam::Vec2,
+    pub max_rotation: f32,
+    // Trauma removed per second
+    pub decay: f32,
+}
+
+impl Default for CameraShake {
+    fn default() -> Self {
+        Self {
+            max_offset: glam::vec2(10.0, 10.0),
+            max_rotation: 0.1,
+            decay: 1.0,
+        }
+    }
+}
+
 pub struct Camera2D {
     pub scale_x: f32,
     pub scale_y: f32,
     pub offset: glam::Vec2,
     pub scale_mode: ScaleMode,
     pub anchor_mode: AnchorMode,
+    // Normalized area of the window the camera draws into
+    pub viewport: Rect,
+    // Cameras are drawn from the lowest order to the highest
+    pub order: i32,
+    // Only sprites and labels with a layer in the mask are drawn by this camera
+    pub layer_mask: u32,
+    pub zoom: f32,
+    pub rotation: f32,
+    pub follow: Option<CameraFollow>,
+    pub bounds: Option<Rect>,
+    pub trauma: f32,
+    pub shake: CameraShake,
+    pub(crate) shake_offset: glam::Vec2,
+    pub(crate) shake_rotation: f32,
+}
+
+impl Default for Camera2D {
+    fn default() -> Self {
+        Self {
+            scale_x: 0.0,
+            scale_y: 0.0,
+            offset: glam::Vec2::ZERO,
+            scale_mode: ScaleMode::default(),
+            anchor_mode: AnchorMode::default(),
+            viewport: Rect::new(0.0, 0.0, 1.0, 1.0),
+            order: 0,
+            layer_mask: ALL_LAYERS,
+            zoom: 1.0,
+            rotation: 0.0,
+            follow: None,
+            bounds: None,
+            trauma: 0.0,
+            shake: CameraShake::default(),
+            shake_offset: glam::Vec2::ZERO,
+            shake_rotation: 0.0,
+        }
+    }
 }
 
 impl Camera2D {
@@ -68,21 +142,116 @@ impl Camera2D {
         }
     }
 
+    pub fn with_follow(mut self, follow: CameraFollow) -> Camera2D {
+        self.follow = Some(follow);
+        self
+    }
+
+    pub fn with_bounds(mut self, bounds: Rect) -> Camera2D {
+        self.bounds = Some(bounds);
+        self
+    }
+
+    pub fn with_zoom(mut self, zoom: f32) -> Camera2D {
+        self.zoom = zoom;
+        self
+    }
+
+    pub fn with_viewport(mut self, viewport: Rect) -> Camera2D {
+        self.viewport = viewport;
+        self
+    }
+
+    pub fn with_order(mut self, order: i32) -> Camera2D {
+        self.order = order;
+        self
+    }
+
+    pub fn with_layer_mask(mut self, layer_mask: u32) -> Camera2D {
+        self.layer_mask = layer_mask;
+        self
+    }
+
+    pub(crate) fn get_viewport(&self, window_size: &glam::UVec2) -> Rect {
+        let window_size = window_size.as_vec2();
+        Rect::new(
+            self.viewport.x * window_size.x,
+            self.viewport.y * window_size.y,
+            self.viewport.width * window_size.x,
+            self.viewport.height * window_size.y,
+        )
+    }
+
+    // Render and window sizes as seen from inside the viewport
+    pub(crate) fn get_viewport_sizes(
+        &self,
+        render_size: &glam::UVec2,
+        window_size: &glam::UVec2,
+    ) -> (glam::UVec2, glam::UVec2) {
+        let scale: glam::Vec2 = self.viewport.size().into();
+        (
+            (render_size.as_vec2() * scale)
+                .as_uvec2()
+                .max(glam::UVec2::ONE),
+            (window_size.as_vec2() * scale)
+                .as_uvec2()
+                .max(glam::UVec2::ONE),
+        )
+    }
+
+    pub(crate) fn get_view(
+        &self,
+        render_size: &glam::UVec2,
+        window_size: &glam::UVec2,
+        position: &glam::Vec2,
+    ) -> CameraView {
+        let viewport = self.get_viewport(window_size);
+        let (render_size, window_size) = self.get_viewport_sizes(render_size, window_size);
+
+        let projection = self.get_view_matrix(&render_size, &window_size, position);
+
+        CameraView {
+            order: self.order,
+            projection,
+            view_rect: self.get_view_rect(&render_size, &window_size, position),
+            cull_rect: get_cull_rect(&projection),
+            viewport,
+            layer_mask: self.layer_mask,
+        }
+    }
+
+    pub fn add_trauma(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma).clamp(0.0, 1.0);
+    }
+
     pub(crate) fn get_view_matrix(
         &self,
         render_size: &glam::UVec2,
         window_size: &glam::UVec2,
         position: &glam::Vec2,
     ) -> glam::Mat4 {
-        let rect = self.get_view_rect(render_size, window_size, position);
-        glam::Mat4::orthographic_rh_gl(
+        let rect = self
+            .get_view_rect(render_size, window_size, position)
+            .translated(self.shake_offset.into());
+        let projection = glam::Mat4::orthographic_rh_gl(
             rect.x,
             rect.width + rect.x,
             rect.height + rect.y,
             rect.y,
             -1.0,
             1.0,
-        )
+        );
+
+        let rotation = self.rotation + self.shake_rotation;
+        if rotation == 0.0 {
+            projection
+        } else {
+            let center: glam::Vec2 = rect.center().into();
+            projection
+                * glam::Mat4::from_translation(center.extend(0.0))
+                * glam::Mat4::from_rotation_z(-rotation)
+                * glam::Mat4::from_translation(-center.extend(0.0))
+        }
     }
 
     pub fn get_view_rect(
@@ -127,6 +296,80 @@ impl Camera2D {
             rect.y -= half_size_y;
         }
 
+        // Zoom keeps the view centered
+        if self.zoom > 0.0 && self.zoom != 1.0 {
+            let center = rect.center();
+            rect.width /= self.zoom;
+            rect.height /= self.zoom;
+            rect.x = center.x - rect.width / 2.0;
+            rect.y = center.y - rect.height / 2.0;
+        }
+
         rect
     }
 }
+
+pub(crate) struct CameraView {
+    pub(crate) order: i32,
+    pub(crate) projection: glam::Mat4,
+    pub(crate) view_rect: Rect,
+    // Bounding box of everything the camera can see, including rotation and shake
+    pub(crate) cull_rect: Rect,
+    pub(crate) viewport: Rect,
+    pub(crate) layer_mask: u32,
+}
+
+impl CameraView {
+    pub(crate) fn screen_to_world(&self, screen: glam::Vec2) -> glam::Vec2 {
+        let viewport_position: glam::Vec2 = self.viewport.position().into();
+        let viewport_size: glam::Vec2 = self.viewport.size().into();
+        let ndc = (screen - viewport_position) / viewport_size * 2.0 - glam::Vec2::ONE;
+
+        self.projection
+            .inverse()
+            .project_point3(glam::vec3(ndc.x, -ndc.y, 0.0))
+            .truncate()
+    }
+
+    pub(crate) fn world_to_screen(&self, world: glam::Vec2) -> glam::Vec2 {
+        let viewport_position: glam::Vec2 = self.viewport.position().into();
+        let viewport_size: glam::Vec2 = self.viewport.size().into();
+        let ndc = self.projection.project_point3(world.extend(0.0));
+
+        viewport_position + (glam::vec2(ndc.x, -ndc.y) + glam::Vec2::ONE) / 2.0 * viewport_size
+    }
+}
+
+pub(crate) fn get_camera_views(
+    world: &hecs::World,
+    render_size: &glam::UVec2,
+    window_size: &glam::UVec2,
+) -> Vec<CameraView> {
+    let mut views = world
+        .query::<(&Camera2D, &Transform2D)>()
+        .iter()
+        .map(|(_id, (camera, transform))| {
+            camera.get_view(render_size, window_size, &transform.position)
+        })
+        .collect::<Vec<_>>();
+    views.sort_by_key(|view| view.order);
+    views
+}
+
+pub(crate) fn get_cull_rect(projection: &glam::Mat4) -> Rect {
+    let inverse = projection.inverse();
+    let (min, max) = [
+        glam::vec3(-1.0, -1.0, 0.0),
+        glam::vec3(1.0, -1.0, 0.0),
+        glam::vec3(1.0, 1.0, 0.0),
+        glam::vec3(-1.0, 1.0, 0.0),
+    ]
+    .iter()
+    .map(|corner| inverse.project_point3(*corner).truncate())
+    .fold(
+        (glam::Vec2::splat(f32::MAX), glam::Vec2::splat(f32::MIN)),
+        |(min, max), corner| (min.min(corner), max.max(corner)),
+    );
+
+    Rect::new(min.x, min.y, max.x - min.x, max.y - min.y)
+}
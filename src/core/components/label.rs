@@ -1,14 +1,83 @@
-use crate::{core::asset_manager::FontId, render::texture::Texture};
+use std::collections::HashMap;
+
+use crate::{
+    core::{
+        asset_manager::{FontId, TextureId},
+        components::{sprite::vertices_bounds, SubTexture},
+        localization::Argument,
+        texture_atlas::TextureAtlas,
+    },
+    font::{layout::TextLayoutOptions, markup::TextStyle, Font, GlyphQuad},
+    render::texture::Texture,
+    shapes::rectangle::Rect,
+};
+
+pub use crate::font::{
+    layout::{TextAlign, VerticalAlign},
+    markup::{parse_markup, GlyphAnimation, SpanContent, TextSpan},
+    sdf::{TextEffects, TextGlow, TextOutline, TextShadow},
+};
 
 pub struct Label {
     pub text: String,
     pub font_id: Option<FontId>,
     pub size: u32,
+    // Box the text is wrapped and aligned in, 0 means unbounded
     pub width: f32,
     pub height: f32,
     pub color: Option<glam::Vec4>,
     pub texture: Option<Texture>,
     pub visible: bool,
+    pub layer: u32,
+    pub align: TextAlign,
+    pub vertical_align: VerticalAlign,
+    // Multiplier of the font line spacing
+    pub line_height: f32,
+    // Pixels added between two glyphs
+    pub letter_spacing: f32,
+    // Wraps the lines at the width, otherwise they are cut
+    pub wrap: bool,
+    // Ends the cut lines with "..."
+    pub ellipsis: bool,
+    // Outline and glow are only drawn with distance field fonts
+    pub effects: TextEffects,
+    // Parses the text for tags like [color=#ff0], see parse_markup
+    pub markup: bool,
+    // Fonts of the [font=name] tags, [b] uses the font named "bold" when there's one
+    pub fonts: HashMap<String, FontId>,
+    // Images of the [img=name] tags, the whole texture when there's no sub texture
+    pub images: HashMap<String, (TextureId, Option<SubTexture>)>,
+    // Seconds since the label was created, drives the markup animations
+    pub time: f32,
+    // Localization key and arguments, the text is replaced with the string of the current
+    // language
+    pub key: Option<String>,
+    pub args: Vec<(String, Argument)>,
+    // Revision of the string tables the text was resolved with
+    pub localized_revision: Option<u32>,
+    pub cache: LabelCache,
+}
+
+// Glyphs of the text laid out with the transform, rebuilt by the sprite system when the
+// label changes or moves. Markup labels and fonts with fallbacks are laid out every frame
+// instead, their spans depend on the images and fonts of the asset manager
+#[derive(Default)]
+pub struct LabelCache {
+    pub(crate) glyphs: Vec<GlyphQuad>,
+    // World space bounding box of the text box, used for culling
+    pub(crate) bounds: Rect,
+    layout_key: Option<LayoutKey>,
+}
+
+// What the cached glyphs were laid out with
+struct LayoutKey {
+    text: String,
+    font_id: Option<FontId>,
+    size: u32,
+    options: TextLayoutOptions,
+    position: glam::Vec2,
+    rotate: f32,
+    scale: glam::Vec2,
 }
 
 impl Default for Label {
@@ -22,6 +91,22 @@ impl Default for Label {
             color: None,
             texture: None,
             visible: true,
+            layer: 1,
+            align: TextAlign::Left,
+            vertical_align: VerticalAlign::Top,
+            line_height: 1.0,
+            letter_spacing: 0.0,
+            wrap: true,
+            ellipsis: false,
+            effects: TextEffects::default(),
+            markup: false,
+            fonts: HashMap::new(),
+            images: HashMap::new(),
+            time: 0.0,
+            key: None,
+            args: Vec::new(),
+            localized_revision: None,
+            cache: LabelCache::default(),
         }
     }
 }
@@ -43,4 +128,200 @@ impl Label {
             ..Default::default()
         }
     }
+
+    // The text is resolved with AssetManager::localize and follows the language changes
+    pub fn new_localized(key: &str, size: u32) -> Label {
+        Label {
+            key: Some(key.to_string()),
+            size,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_arg<A: Into<Argument>>(mut self, name: &str, value: A) -> Self {
+        self.set_arg(name, value);
+        self
+    }
+
+    pub fn get_key(&self) -> Option<&str> {
+        self.key.as_deref()
+    }
+
+    pub fn set_key(&mut self, key: &str) {
+        self.key = Some(key.to_string());
+        self.localized_revision = None;
+    }
+
+    pub fn set_arg<A: Into<Argument>>(&mut self, name: &str, value: A) {
+        let value = value.into();
+        match self.args.iter_mut().find(|(arg, _)| arg == name) {
+            Some((_, arg)) if *arg == value => return,
+            Some((_, arg)) => *arg = value,
+            None => self.args.push((name.to_string(), value)),
+        }
+        self.localized_revision = None;
+    }
+
+    pub fn with_box(mut self, width: f32, height: f32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn with_align(mut self, align: TextAlign, vertical_align: VerticalAlign) -> Self {
+        self.align = align;
+        self.vertical_align = vertical_align;
+        self
+    }
+
+    pub fn with_outline(mut self, width: f32, color: glam::Vec4) -> Self {
+        self.effects.outline = Some(TextOutline { width, color });
+        self
+    }
+
+    pub fn with_glow(mut self, radius: f32, color: glam::Vec4) -> Self {
+        self.effects.glow = Some(TextGlow { radius, color });
+        self
+    }
+
+    pub fn with_shadow(mut self, offset: glam::Vec2, color: glam::Vec4, softness: f32) -> Self {
+        self.effects.shadow = Some(TextShadow {
+            offset,
+            color,
+            softness,
+        });
+        self
+    }
+
+    pub fn with_markup(mut self) -> Self {
+        self.markup = true;
+        self
+    }
+
+    pub fn with_font(mut self, name: &str, font_id: FontId) -> Self {
+        self.fonts.insert(name.to_string(), font_id);
+        self
+    }
+
+    pub fn with_image(
+        mut self,
+        name: &str,
+        texture_id: TextureId,
+        sub_texture: Option<SubTexture>,
+    ) -> Self {
+        self.images
+            .insert(name.to_string(), (texture_id, sub_texture));
+        self
+    }
+
+    // Every region of the atlas can be used by name
+    pub fn with_atlas_images(mut self, atlas: &TextureAtlas) -> Self {
+        for (name, (texture_id, sub_texture)) in &atlas.regions {
+            self.images
+                .insert(name.clone(), (texture_id.clone(), Some(*sub_texture)));
+        }
+        self
+    }
+
+    // Font of a markup span, None for the label font. The bool is true when the span is
+    // bold without a bold font
+    pub(crate) fn span_font(&self, style: &TextStyle) -> (Option<&FontId>, bool) {
+        let font = style
+            .font
+            .as_ref()
+            .and_then(|name| self.fonts.get(name))
+            .or(self.font_id.as_ref());
+        match (style.bold, style.font.is_none(), self.fonts.get("bold")) {
+            (true, true, Some(bold)) => (Some(bold), false),
+            (bold, _, _) => (font, bold),
+        }
+    }
+
+    // The parsed markup, or the whole text with the label style
+    pub(crate) fn spans(&self) -> Vec<TextSpan> {
+        if self.markup {
+            parse_markup(&self.text)
+        } else {
+            vec![TextSpan {
+                content: SpanContent::Text(self.text.clone()),
+                style: TextStyle::default(),
+            }]
+        }
+    }
+
+    pub(crate) fn needs_update(
+        &self,
+        position: glam::Vec2,
+        rotate: f32,
+        scale: glam::Vec2,
+    ) -> bool {
+        match &self.cache.layout_key {
+            Some(key) => {
+                key.position != position
+                    || key.rotate != rotate
+                    || key.scale != scale
+                    || key.size != self.size
+                    || key.font_id != self.font_id
+                    || key.text != self.text
+                    || key.options != self.layout_options()
+            }
+            None => true,
+        }
+    }
+
+    pub(crate) fn update_vertices(
+        &mut self,
+        font: &Font,
+        position: glam::Vec2,
+        rotate: f32,
+        scale: glam::Vec2,
+    ) {
+        let options = self.layout_options();
+        let layout = font.layout(&self.text, self.size, &options);
+        let size = match (options.max_width, options.max_height) {
+            (Some(width), Some(height)) => glam::vec2(width, height),
+            _ => layout.size,
+        };
+
+        let transform = glam::Affine2::from_scale_angle_translation(scale, rotate, position);
+        self.cache.bounds = layout_bounds(size, transform);
+        self.cache.glyphs = font.glyph_quads(&layout, transform, self.size);
+        self.cache.layout_key = Some(LayoutKey {
+            text: self.text.clone(),
+            font_id: self.font_id.clone(),
+            size: self.size,
+            options,
+            position,
+            rotate,
+            scale,
+        });
+    }
+
+    pub(crate) fn layout_options(&self) -> TextLayoutOptions {
+        TextLayoutOptions {
+            max_width: Some(self.width).filter(|width| *width > 0.0),
+            max_height: Some(self.height).filter(|height| *height > 0.0),
+            align: self.align,
+            vertical_align: self.vertical_align,
+            line_height: self.line_height,
+            letter_spacing: self.letter_spacing,
+            wrap: self.wrap,
+            ellipsis: self.ellipsis,
+        }
+    }
+}
+
+// World space bounding box of text laid out in a box of the size
+pub(crate) fn layout_bounds(size: glam::Vec2, transform: glam::Affine2) -> Rect {
+    let corners = [
+        glam::Vec2::ZERO,
+        size * glam::Vec2::X,
+        size,
+        size * glam::Vec2::Y,
+    ];
+    vertices_bounds(
+        corners
+            .into_iter()
+            .map(|corner| transform.transform_point2(corner).extend(0.0)),
+    )
 }
@@ -0,0 +1,91 @@
+use crate::shapes::rectangle::Rect;
+
+pub struct PointLight2D {
+    pub color: glam::Vec3,
+    pub intensity: f32,
+    pub radius: f32,
+    // Distance from the light to the sprites, only used by normal maps
+    pub height: f32,
+    // Radius of the light source, 0.0 casts hard shadows
+    pub shadow_softness: f32,
+    pub enabled: bool,
+}
+
+impl Default for PointLight2D {
+    fn default() -> Self {
+        Self {
+            color: glam::Vec3::ONE,
+            intensity: 1.0,
+            radius: 100.0,
+            height: 50.0,
+            shadow_softness: 0.0,
+            enabled: true,
+        }
+    }
+}
+
+impl PointLight2D {
+    pub fn new(color: glam::Vec3, radius: f32) -> PointLight2D {
+        PointLight2D {
+            color,
+            radius,
+            ..Default::default()
+        }
+    }
+}
+
+// Points along the Transform2D rotation
+pub struct SpotLight2D {
+    pub color: glam::Vec3,
+    pub intensity: f32,
+    pub radius: f32,
+    pub height: f32,
+    pub shadow_softness: f32,
+    // Half angles in radians, full intensity inside the inner angle fading out until the outer one
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+    pub enabled: bool,
+}
+
+impl Default for SpotLight2D {
+    fn default() -> Self {
+        Self {
+            color: glam::Vec3::ONE,
+            intensity: 1.0,
+            radius: 200.0,
+            height: 50.0,
+            shadow_softness: 0.0,
+            inner_angle: 0.3,
+            outer_angle: 0.5,
+            enabled: true,
+        }
+    }
+}
+
+impl SpotLight2D {
+    pub fn new(color: glam::Vec3, radius: f32, angle: f32) -> SpotLight2D {
+        SpotLight2D {
+            color,
+            radius,
+            inner_angle: angle * 0.75,
+            outer_angle: angle,
+            ..Default::default()
+        }
+    }
+}
+
+pub struct LightOccluder2D {
+    // Moved, rotated and scaled by the Transform2D. Shadows are cast by axis aligned
+    // rectangles, so a rotated occluder blocks the light with its bounding box
+    pub rect: Rect,
+    pub enabled: bool,
+}
+
+impl LightOccluder2D {
+    pub fn new(rect: Rect) -> LightOccluder2D {
+        LightOccluder2D {
+            rect,
+            enabled: true,
+        }
+    }
+}
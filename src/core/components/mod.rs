@@ -1,12 +1,21 @@
 pub mod animated_sprite;
 pub mod camera2d;
 pub mod label;
+pub mod light2d;
+pub mod parallax;
+pub mod particle_emitter2d;
 pub mod sprite;
 pub mod transform2d;
 
-pub use crate::render::subtexture::SubTexture;
+pub use crate::render::{
+    nine_slice::{NineSlice, SliceFill},
+    subtexture::SubTexture,
+};
 pub use animated_sprite::*;
 pub use camera2d::*;
 pub use label::*;
+pub use light2d::*;
+pub use parallax::*;
+pub use particle_emitter2d::*;
 pub use sprite::*;
 pub use transform2d::*;
@@ -0,0 +1,33 @@
+// Moves the sprite against the first camera whose layer mask draws it, updated after
+// the cameras. A layer seen by several cameras only follows that first one, and it
+// isn't rotated with the camera, a rotating view turns it like the rest of the world
+pub struct ParallaxLayer {
+    // 1.0 moves with the world, 0.0 stays fixed to the camera
+    pub factor: glam::Vec2,
+    // Repeats the sprite across the whole view, the sprite is drawn as SpriteMode::Tiled.
+    // Nine-slice sprites can't repeat, a warning is logged and both flags are cleared
+    pub repeat_x: bool,
+    pub repeat_y: bool,
+    // Position with the camera view at the origin, taken from the Transform2D when None
+    pub origin: Option<glam::Vec2>,
+    // Tiled offset added by the last update, scrolling offsets are kept
+    pub(crate) applied_offset: glam::Vec2,
+}
+
+impl ParallaxLayer {
+    pub fn new(factor: glam::Vec2) -> ParallaxLayer {
+        ParallaxLayer {
+            factor,
+            repeat_x: false,
+            repeat_y: false,
+            origin: None,
+            applied_offset: glam::Vec2::ZERO,
+        }
+    }
+
+    pub fn with_repeat(mut self, repeat_x: bool, repeat_y: bool) -> Self {
+        self.repeat_x = repeat_x;
+        self.repeat_y = repeat_y;
+        self
+    }
+}
@@ -0,0 +1,192 @@
+use std::ops::{Add, Mul};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{core::asset_manager::TextureId, render::subtexture::SubTexture};
+
+// Keys are (normalized lifetime, value) pairs sorted by time, values in between are interpolated
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Curve<T> {
+    pub keys: Vec<(f32, T)>,
+}
+
+impl<T> Curve<T>
+where
+    T: Copy + Add<Output = T> + Mul<f32, Output = T>,
+{
+    pub fn new(keys: Vec<(f32, T)>) -> Curve<T> {
+        Curve { keys }
+    }
+
+    pub fn constant(value: T) -> Curve<T> {
+        Curve {
+            keys: vec![(0.0, value)],
+        }
+    }
+
+    pub fn sample(&self, time: f32, default: T) -> T {
+        let (first, last) = match (self.keys.first(), self.keys.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return default,
+        };
+        if time <= first.0 {
+            return first.1;
+        }
+        if time >= last.0 {
+            return last.1;
+        }
+
+        for keys in self.keys.windows(2) {
+            let (from, to) = (keys[0], keys[1]);
+            if time <= to.0 {
+                let t = (time - from.0) / (to.0 - from.0).max(f32::EPSILON);
+                return from.1 * (1.0 - t) + to.1 * t;
+            }
+        }
+
+        last.1
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ParticleBurst {
+    // Seconds since the emitter started
+    pub time: f32,
+    pub count: usize,
+}
+
+// Emitter preset, can be loaded from JSON with AssetManager::load_particle_emitter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParticleEmitterSettings {
+    pub max_particles: usize,
+    // Particles per second
+    pub emission_rate: f32,
+    // Sorted by time
+    pub bursts: Vec<ParticleBurst>,
+    // Seconds until the emitter stops, restarted when looping
+    pub duration: f32,
+    pub looping: bool,
+    // Ranges are (min, max), picked randomly for each particle
+    pub lifetime: (f32, f32),
+    pub speed: (f32, f32),
+    pub angular_velocity: (f32, f32),
+    // Center of the emission cone in radians, added to the Transform2D rotation
+    pub direction: f32,
+    // Half angle of the emission cone in radians
+    pub spread: f32,
+    pub gravity: glam::Vec2,
+    // Particle size in pixels before the scale curve
+    pub size: glam::Vec2,
+    pub color: Curve<glam::Vec4>,
+    pub scale: Curve<f32>,
+    // Particles keep moving in world space when the emitter moves, otherwise they follow it
+    pub world_space: bool,
+}
+
+impl Default for ParticleEmitterSettings {
+    fn default() -> Self {
+        Self {
+            max_particles: 1000,
+            emission_rate: 10.0,
+            bursts: Vec::new(),
+            duration: 1.0,
+            looping: true,
+            lifetime: (1.0, 1.0),
+            speed: (50.0, 50.0),
+            angular_velocity: (0.0, 0.0),
+            direction: 0.0,
+            spread: std::f32::consts::PI,
+            gravity: glam::Vec2::ZERO,
+            size: glam::vec2(4.0, 4.0),
+            color: Curve::constant(glam::Vec4::ONE),
+            scale: Curve::constant(1.0),
+            world_space: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Particle {
+    pub position: glam::Vec2,
+    pub velocity: glam::Vec2,
+    pub rotation: f32,
+    pub angular_velocity: f32,
+    pub age: f32,
+    pub lifetime: f32,
+}
+
+pub struct ParticleEmitter2D {
+    pub settings: ParticleEmitterSettings,
+    pub texture_id: Option<TextureId>,
+    pub sub_texture: Option<SubTexture>,
+    pub emitting: bool,
+    // Bit mask matched against the camera layer_mask
+    pub layer: u32,
+    pub lit: bool,
+    pub(crate) particles: Vec<Particle>,
+    pub(crate) time: f32,
+    pub(crate) emit_accumulator: f32,
+    pub(crate) next_burst: usize,
+    pub(crate) pending_burst: usize,
+}
+
+impl Default for ParticleEmitter2D {
+    fn default() -> Self {
+        ParticleEmitter2D::new(ParticleEmitterSettings::default())
+    }
+}
+
+impl ParticleEmitter2D {
+    pub fn new(settings: ParticleEmitterSettings) -> ParticleEmitter2D {
+        ParticleEmitter2D {
+            settings,
+            texture_id: None,
+            sub_texture: None,
+            emitting: true,
+            layer: 1,
+            lit: false,
+            particles: Vec::new(),
+            time: 0.0,
+            emit_accumulator: 0.0,
+            next_burst: 0,
+            pending_burst: 0,
+        }
+    }
+
+    pub fn with_texture(mut self, texture_id: TextureId) -> Self {
+        self.texture_id = Some(texture_id);
+        self
+    }
+
+    pub fn with_sub_texture(mut self, texture_id: TextureId, sub_texture: SubTexture) -> Self {
+        self.texture_id = Some(texture_id);
+        self.sub_texture = Some(sub_texture);
+        self
+    }
+
+    // Emits count particles on the next update, even when the emitter is not emitting
+    pub fn burst(&mut self, count: usize) {
+        self.pending_burst += count;
+    }
+
+    // Clears the particles and the bursts requested before the restart
+    pub fn restart(&mut self) {
+        self.particles.clear();
+        self.time = 0.0;
+        self.emit_accumulator = 0.0;
+        self.next_burst = 0;
+        self.pending_burst = 0;
+        self.emitting = true;
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    // A non looping emitter that has stopped and has no particles alive
+    pub fn is_finished(&self) -> bool {
+        !self.emitting && self.pending_burst == 0 && self.particles.is_empty()
+    }
+}
@@ -1,11 +1,59 @@
 use crate::{
     core::asset_manager::TextureId,
     render::{
+        nine_slice::{nine_slice_quads, tiled_quads, NineSlice, SliceQuad},
         subtexture::SubTexture,
         vertex::{CENTER_QUAD, TOP_LEFT_QUAD},
     },
+    shapes::rectangle::Rect,
 };
 
+// Only Stretch places trimmed and rotated sprite sheet frames, the other modes
+// slice the region as it is packed in the texture
+#[derive(Debug, Clone, Copy)]
+pub enum SpriteMode {
+    // Scales the whole texture or sub texture region to the sprite size
+    Stretch,
+    NineSlice(NineSlice),
+    // Repeats the texture or sub texture region across the sprite size
+    Tiled {
+        // Texels the pattern is scrolled by
+        offset: glam::Vec2,
+        // Added to the offset every second
+        scroll_speed: glam::Vec2,
+    },
+}
+
+impl SpriteMode {
+    pub fn tiled() -> SpriteMode {
+        SpriteMode::Tiled {
+            offset: glam::Vec2::ZERO,
+            scroll_speed: glam::Vec2::ZERO,
+        }
+    }
+
+    pub fn scrolling(scroll_speed: glam::Vec2) -> SpriteMode {
+        SpriteMode::Tiled {
+            offset: glam::Vec2::ZERO,
+            scroll_speed,
+        }
+    }
+}
+
+impl Default for SpriteMode {
+    fn default() -> Self {
+        SpriteMode::Stretch
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SpriteQuad {
+    pub vertices: [glam::Vec3; 4],
+    pub texture_coords: [glam::Vec2; 4],
+    // Sampled with a repeating sampler, texture coords go past 0..1
+    pub wrap: bool,
+}
+
 #[derive(Debug)]
 pub struct Sprite {
     pub texture_id: Option<TextureId>,
@@ -15,7 +63,29 @@ pub struct Sprite {
     pub center_origin: bool,
     pub flip_x: bool,
     pub flip_y: bool,
+    pub mode: SpriteMode,
+    // Size in pixels before the transform scale, defaults to the texture or sub texture size
+    pub size: Option<glam::Vec2>,
+    // Bit mask matched against the camera layer_mask
+    pub layer: u32,
+    // Tangent space normal map sampled with the same texture coordinates, loaded with
+    // AssetManager::load_normal_map
+    pub normal_map: Option<TextureId>,
+    // Whether the sprite is affected by 2D lights
+    pub lit: bool,
     pub vertices: [glam::Vec3; 4],
+    pub cache: SpriteCache,
+}
+
+// Render data derived from the sprite and its transform, rebuilt by the sprite system
+#[derive(Debug, Default)]
+pub struct SpriteCache {
+    // Maps the unit quad to the vertices, used by the instanced path
+    pub(crate) transform: glam::Affine2,
+    // Used instead of vertices by the modes that need more than one quad
+    pub(crate) quads: Vec<SpriteQuad>,
+    // World space bounding box of the vertices, used for culling
+    pub(crate) bounds: Rect,
 }
 
 impl Default for Sprite {
@@ -28,7 +98,13 @@ impl Default for Sprite {
             center_origin: false,
             flip_x: false,
             flip_y: false,
+            mode: SpriteMode::Stretch,
+            size: None,
+            layer: 1,
+            normal_map: None,
+            lit: true,
             vertices: [glam::Vec3::ZERO; 4],
+            cache: SpriteCache::default(),
         }
     }
 }
@@ -47,11 +123,58 @@ impl Sprite {
             &TOP_LEFT_QUAD
         };
 
-        let render_rect_size = if let Some(sub_texture) = &self.sub_texture {
-            sub_texture.region.size().into()
-        } else {
-            texture_size
+        let (region, frame_size) = match &self.sub_texture {
+            Some(sub_texture) => (sub_texture.region, sub_texture.frame_size()),
+            None => (
+                Rect::new(0.0, 0.0, texture_size.x, texture_size.y),
+                texture_size,
+            ),
         };
+        let render_rect_size = self.size.unwrap_or(frame_size);
+
+        // The pattern repeats every region, wrapping keeps the scrolled offset precise
+        if let SpriteMode::Tiled { offset, .. } = &mut self.mode {
+            if region.width > 0.0 && region.height > 0.0 {
+                offset.x = offset.x.rem_euclid(region.width);
+                offset.y = offset.y.rem_euclid(region.height);
+            }
+        }
+
+        self.cache.quads.clear();
+        match self.mode {
+            SpriteMode::Stretch => {}
+            SpriteMode::NineSlice(nine_slice) => {
+                let quads = nine_slice_quads(&nine_slice, render_rect_size, region, texture_size);
+                self.update_quads(&quads, false, render_rect_size, position, rotate, scale);
+                return;
+            }
+            // Whole textures use a single quad with a wrapping sampler,
+            // atlas regions can't wrap so they get one quad per repetition
+            SpriteMode::Tiled { offset, .. } if self.sub_texture.is_none() => {
+                let quad = SliceQuad::new(
+                    (
+                        0.0,
+                        render_rect_size.x,
+                        offset.x,
+                        offset.x + render_rect_size.x,
+                    ),
+                    (
+                        0.0,
+                        render_rect_size.y,
+                        offset.y,
+                        offset.y + render_rect_size.y,
+                    ),
+                    texture_size,
+                );
+                self.update_quads(&[quad], true, render_rect_size, position, rotate, scale);
+                return;
+            }
+            SpriteMode::Tiled { offset, .. } => {
+                let quads = tiled_quads(render_rect_size, region, texture_size, offset);
+                self.update_quads(&quads, false, render_rect_size, position, rotate, scale);
+                return;
+            }
+        }
 
         let transform = if rotate == 0.0 {
             glam::Mat4::from_translation(position.extend(0.0))
@@ -64,13 +187,81 @@ impl Sprite {
             )
         };
 
-        self.vertices[0] = (transform * quad[0]).truncate();
-        self.vertices[1] = (transform * quad[1]).truncate();
-        self.vertices[2] = (transform * quad[2]).truncate();
-        self.vertices[3] = (transform * quad[3]).truncate();
+        // Trimmed frames only cover their part of the frame rect
+        let (content_offset, content_size) = self
+            .sub_texture
+            .map_or((glam::Vec2::ZERO, glam::Vec2::ONE), |sub_texture| {
+                sub_texture.content_rect()
+            });
+        let top_left = quad[1].truncate().truncate();
+        let corner = |corner: glam::Vec4| {
+            let point = top_left
+                + content_offset
+                + (corner.truncate().truncate() - top_left) * content_size;
+            (transform * point.extend(0.0).extend(1.0)).truncate()
+        };
+
+        self.vertices[0] = corner(quad[0]);
+        self.vertices[1] = corner(quad[1]);
+        self.vertices[2] = corner(quad[2]);
+        self.vertices[3] = corner(quad[3]);
+
+        self.cache.transform =
+            glam::Affine2::from_scale_angle_translation(render_rect_size * scale, rotate, position)
+                * glam::Affine2::from_translation(top_left + content_offset)
+                * glam::Affine2::from_scale(content_size);
+
+        self.cache.bounds = vertices_bounds(self.vertices.into_iter());
     }
 
-    pub(crate) fn get_vertices(&self) -> &[glam::Vec3; 4] {
-        &self.vertices
+    fn update_quads(
+        &mut self,
+        quads: &[SliceQuad],
+        wrap: bool,
+        size: glam::Vec2,
+        position: glam::Vec2,
+        rotate: f32,
+        scale: glam::Vec2,
+    ) {
+        let origin = if self.center_origin {
+            -size / 2.0
+        } else {
+            glam::Vec2::ZERO
+        };
+        let transform = glam::Mat4::from_scale_rotation_translation(
+            scale.extend(1.0),
+            glam::Quat::from_rotation_z(rotate),
+            position.extend(0.0),
+        );
+        let (flip_x, flip_y) = (self.flip_x, self.flip_y);
+        let to_world = |mut point: glam::Vec2| {
+            if flip_x {
+                point.x = size.x - point.x;
+            }
+            if flip_y {
+                point.y = size.y - point.y;
+            }
+            (transform * (origin + point).extend(0.0).extend(1.0)).truncate()
+        };
+
+        self.cache.quads.extend(quads.iter().map(|quad| SpriteQuad {
+            vertices: quad.positions.map(to_world),
+            texture_coords: quad.texture_coords,
+            wrap,
+        }));
+
+        self.cache.bounds = vertices_bounds(self.cache.quads.iter().flat_map(|quad| quad.vertices));
+    }
+
+    pub fn bounds(&self) -> Rect {
+        self.cache.bounds
     }
 }
+
+pub(crate) fn vertices_bounds(vertices: impl Iterator<Item = glam::Vec3>) -> Rect {
+    let (min, max) = vertices.fold(
+        (glam::Vec2::splat(f32::MAX), glam::Vec2::splat(f32::MIN)),
+        |(min, max), vertex| (min.min(vertex.truncate()), max.max(vertex.truncate())),
+    );
+    Rect::new(min.x, min.y, max.x - min.x, max.y - min.y)
+}
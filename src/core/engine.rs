@@ -271,9 +271,12 @@ impl Engine {
                     // let settings = game_state.take_settings();
                     // WindowSettings::apply_window(&mut self.window.borrow_mut(), settings);
 
-                    match game_state.render(renderer, engine_timer.view_time()) {
-                        Ok(_) => {}
-                        Err(e) => error!("Render Broken {:?}", e),
+                    if let Err(e) = game_state.render(renderer, engine_timer.view_time()) {
+                        error!("Render Broken {:?}", e);
+                        if let Err(e) = game_state.render_error(e) {
+                            error!("Render error not handled, exiting {:?}", e);
+                            *control_flow = ControlFlow::Exit
+                        }
                     }
                 }
                 GameLoopState::Wait => engine_timer.wait(),
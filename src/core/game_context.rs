@@ -1,8 +1,4 @@
-use super::{
-    asset_manager::AudioId,
-    components::{Camera2D, Transform2D},
-    game_window::ReadOnlyRc,
-};
+use super::{asset_manager::AudioId, components::get_camera_views, game_window::ReadOnlyRc};
 use crate::{
     physics2d::physics_world::PhysicsWorld, render::renderer::Renderer, shapes::rectangle::Rect,
 };
@@ -14,10 +10,12 @@ use std::{
 
 pub struct GameContext {
     pub(crate) clear_color: glam::Vec3,
+    pub(crate) ambient_light: Option<glam::Vec3>,
     pub(crate) world: Rc<RefCell<World>>,
     pub(crate) physics_world: Rc<RefCell<PhysicsWorld>>,
     audio_queue: Vec<AudioId>,
-    renderer: ReadOnlyRc<Renderer>,
+    language: Option<String>,
+    pub(crate) renderer: ReadOnlyRc<Renderer>,
 }
 
 impl GameContext {
@@ -26,7 +24,9 @@ impl GameContext {
             world: Rc::new(RefCell::new(World::new())),
             physics_world: Rc::new(RefCell::new(PhysicsWorld::new())),
             clear_color: glam::Vec3::ZERO,
+            ambient_light: None,
             audio_queue: Vec::new(),
+            language: None,
             renderer,
         }
     }
@@ -47,40 +47,59 @@ impl GameContext {
         self.physics_world.borrow_mut()
     }
 
+    // View rect of the main camera, the one with the lowest order
     pub fn get_camera_rect(&self) -> Option<Rect> {
-        // TODO: not yet implemented
-        todo!();
-        // if let Some((_id, (camera, transform))) = self
-        //     .world
-        //     .query::<(&Camera2D, &Transform2D)>()
-        //     .iter()
-        //     .next()
-        // {
-        //     // let window_size = {
-        //     //     let size = self.window.borrow().window().inner_size();
-        //     //     glam::uvec2(size.width, size.height)
-        //     // };
-
-        //     // Some(
-        //     //     camera.get_view_rect(
-        //     //         self.renderer
-        //     //             .borrow()
-        //     //             .render_size()
-        //     //             .as_ref()
-        //     //             .unwrap_or(&window_size),
-        //     //         &window_size,
-        //     //         &transform.position,
-        //     //     ),
-        //     // )
-        // } else {
-        //     None
-        // }
+        let world = self.world.borrow();
+        let renderer = self.renderer.borrow();
+
+        get_camera_views(&world, &renderer.display_size(), &renderer.window_size())
+            .first()
+            .map(|view| view.view_rect)
+    }
+
+    // Maps window pixels, like the ones from Event::CursorMoved, to world space
+    // using the top most camera whose viewport contains the point
+    pub fn screen_to_world(&self, screen: glam::Vec2) -> glam::Vec2 {
+        let world = self.world.borrow();
+        let renderer = self.renderer.borrow();
+        let views = get_camera_views(&world, &renderer.display_size(), &renderer.window_size());
+
+        match views
+            .iter()
+            .rev()
+            .find(|view| view.viewport.contains_point(&screen.into()))
+            .or(views.first())
+        {
+            Some(view) => view.screen_to_world(screen),
+            None => screen * renderer.display_size().as_vec2() / renderer.window_size().as_vec2(),
+        }
+    }
+
+    // Maps a world position to window pixels using the main camera
+    pub fn world_to_screen(&self, position: glam::Vec2) -> glam::Vec2 {
+        let world = self.world.borrow();
+        let renderer = self.renderer.borrow();
+        let views = get_camera_views(&world, &renderer.display_size(), &renderer.window_size());
+
+        match views.first() {
+            Some(view) => view.world_to_screen(position),
+            None => position * renderer.window_size().as_vec2() / renderer.display_size().as_vec2(),
+        }
     }
 
     pub fn set_clear_color(&mut self, color: glam::Vec3) {
         self.clear_color = color;
     }
 
+    // Enables 2D lighting, lit sprites are only lit by the ambient color outside of any light
+    pub fn set_ambient_light(&mut self, color: glam::Vec3) {
+        self.ambient_light = Some(color);
+    }
+
+    pub fn clear_ambient_light(&mut self) {
+        self.ambient_light = None;
+    }
+
     pub fn play_audio(&mut self, audio_id: AudioId) {
         self.audio_queue.push(audio_id);
     }
@@ -88,4 +107,14 @@ impl GameContext {
     pub(crate) fn take_audio_queue(&mut self) -> Vec<AudioId> {
         self.audio_queue.drain(..).collect()
     }
+
+    // Switched at the end of the update, every localized label is updated before the next
+    // frame is drawn
+    pub fn set_language(&mut self, language: &str) {
+        self.language = Some(language.to_string());
+    }
+
+    pub(crate) fn take_language(&mut self) -> Option<String> {
+        self.language.take()
+    }
 }
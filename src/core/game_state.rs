@@ -6,15 +6,20 @@ use super::{
     input::Input,
     scene::{InputHandled, Scene, Transition},
     systems::{
-        animated_sprite::system_update_animated_sprite, font::system_render_font_texture,
-        sprite::system_render_sprite,
+        animated_sprite::system_update_animated_sprite,
+        camera2d::system_update_camera,
+        font::{system_render_font_texture, system_update_label, system_update_localized_label},
+        light2d::system_render_lights,
+        parallax::system_update_parallax,
+        particles::system_update_particles,
+        sprite::{system_render_sprite, system_update_tiled_sprite},
     },
     ui_context::UIContext,
 };
 use crate::{
     audio::AudioPlayer,
     core::{asset_manager::AssetManager, engine_context::EngineContext, game_context::GameContext},
-    error::BreakoutResult,
+    error::{BreakoutError, BreakoutResult},
     font::Font,
     physics2d::systems::physics::system_update_physics,
     render::renderer::Renderer,
@@ -115,6 +120,7 @@ impl GameState {
                 )? {
                     Transition::None => {
                         active_scene.ui(&mut self.context, &mut self.ui_context);
+                        self.ui_context.end_frame();
                     }
                     Transition::Push(s) => {
                         self.scenes.push(s);
@@ -139,7 +145,16 @@ impl GameState {
             let audio = self.asset_manager.get_audio(&audio_queue);
             self.music_player.play(audio);
         }
+        if let Some(language) = self.context.take_language() {
+            self.asset_manager.set_language(&language);
+        }
         system_update_animated_sprite(&self.context, delta);
+        system_update_tiled_sprite(&self.context, delta);
+        system_update_label(&self.context, delta);
+        system_update_localized_label(&self.context, &self.asset_manager);
+        system_update_particles(&self.context, delta);
+        system_update_camera(&self.context, delta);
+        system_update_parallax(&self.context, &self.asset_manager);
 
         result
     }
@@ -150,12 +165,17 @@ impl GameState {
         view_time: EngineTimerView,
     ) -> BreakoutResult {
         let mut renderer_borrowed_mut = renderer.borrow_mut();
+        if renderer_borrowed_mut.is_minimized() {
+            return Ok(());
+        }
+
         system_render_font_texture(
             &self.context,
             &mut self.asset_manager,
             &renderer_borrowed_mut,
             &mut self.default_font,
         )?;
+        system_render_lights(&self.context, &mut renderer_borrowed_mut);
         system_render_sprite(
             &self.context,
             &self.asset_manager,
@@ -163,9 +183,17 @@ impl GameState {
             &self.default_font,
         )?;
 
-        // TODO not yet implemented
-        // self.ui_context
-        //     .render(&mut renderer_borrowed_mut, &view_time);
-        Ok(())
+        self.ui_context
+            .render(&mut renderer_borrowed_mut, &self.asset_manager, &view_time)?;
+        renderer_borrowed_mut.end_draw()
+    }
+
+    pub fn render_error(&mut self, error: BreakoutError) -> BreakoutResult {
+        match self.scenes.last_mut() {
+            Some(active_scene) => {
+                active_scene.render_error(error, &mut self.context, &mut self.engine)
+            }
+            None => Err(error),
+        }
     }
 }
@@ -122,16 +122,6 @@ impl GameWindow {
                 Event::MainEventsCleared => {
                     game_loop(GameLoopState::Update, control_flow);
                     game_loop(GameLoopState::Render(self.renderer_mut()), control_flow);
-                    // TODO put it back
-                    // match renderer.borrow_mut().render() {
-                    //     Ok(_) => {}
-                    //     // Reconfigure the surface if lost
-                    //     Err(wgpu::SurfaceError::Lost) => renderer.borrow_mut().reconfigure(),
-                    //     // The system is out of memory, we should probably quit
-                    //     Err(wgpu::SurfaceError::OutOfMemory) => *control_flow = ControlFlow::Exit,
-                    //     // All other errors (Outdated, Timeout) should be resolved by the next frame
-                    //     Err(e) => error!("{:?}", e),
-                    // }
                 }
                 Event::RedrawRequested(_) => {
                     // windows_id is not required for the engine
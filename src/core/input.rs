@@ -14,6 +14,8 @@ pub enum Event {
         is_pressed: bool,
     },
     MouseWheel(f32),
+    // A typed character, composed input methods send their characters one at a time
+    Text(char),
     CursorMoved(glam::Vec2),
     ModifiersChanged,
 }
@@ -108,6 +110,7 @@ impl Input {
                 position.x as f32,
                 position.y as f32,
             ))),
+            WindowEvent::ReceivedCharacter(c) => Some(Event::Text(*c)),
             WindowEvent::ModifiersChanged(_modifiers_state) => Some(Event::ModifiersChanged),
             _ => None,
         };
@@ -0,0 +1,514 @@
+use std::collections::HashMap;
+
+use crate::error::{BreakoutError, BreakoutResult};
+
+const INVALID_JSON: BreakoutError = BreakoutError::GenericError("Invalid string table");
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Argument {
+    Text(String),
+    Number(f64),
+}
+
+impl From<&str> for Argument {
+    fn from(value: &str) -> Self {
+        Argument::Text(value.to_string())
+    }
+}
+
+impl From<String> for Argument {
+    fn from(value: String) -> Self {
+        Argument::Text(value)
+    }
+}
+
+macro_rules! number_argument {
+    ($($type:ty),*) => {
+        $(impl From<$type> for Argument {
+            fn from(value: $type) -> Self {
+                Argument::Number(value as f64)
+            }
+        })*
+    };
+}
+
+number_argument!(i32, i64, u32, u64, usize, f32, f64);
+
+impl std::fmt::Display for Argument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Argument::Text(text) => write!(f, "{}", text),
+            Argument::Number(number) => write!(f, "{}", number),
+        }
+    }
+}
+
+// CLDR plural categories, each language only uses some of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PluralCategory {
+    Zero,
+    One,
+    Two,
+    Few,
+    Many,
+    Other,
+}
+
+impl PluralCategory {
+    fn from_name(name: &str) -> Option<PluralCategory> {
+        match name {
+            "zero" => Some(PluralCategory::Zero),
+            "one" => Some(PluralCategory::One),
+            "two" => Some(PluralCategory::Two),
+            "few" => Some(PluralCategory::Few),
+            "many" => Some(PluralCategory::Many),
+            "other" => Some(PluralCategory::Other),
+            _ => None,
+        }
+    }
+
+    // Cardinal rules of the common languages, the others use the english rule
+    pub fn of(language: &str, number: f64) -> PluralCategory {
+        let language = language
+            .split(|c| c == '-' || c == '_')
+            .next()
+            .unwrap_or(language)
+            .to_ascii_lowercase();
+        if number.fract() != 0.0 {
+            return match language.as_str() {
+                "cs" | "sk" => PluralCategory::Many,
+                _ => PluralCategory::Other,
+            };
+        }
+        let n = number.abs() as u64;
+        let (n10, n100) = (n % 10, n % 100);
+        match language.as_str() {
+            "ja" | "zh" | "ko" | "vi" | "th" | "id" | "ms" => PluralCategory::Other,
+            "fr" | "pt" => match n {
+                0 | 1 => PluralCategory::One,
+                _ => PluralCategory::Other,
+            },
+            "ru" | "uk" | "be" => match (n10, n100) {
+                (1, n100) if n100 != 11 => PluralCategory::One,
+                (2..=4, n100) if !(12..=14).contains(&n100) => PluralCategory::Few,
+                _ => PluralCategory::Many,
+            },
+            "pl" => match (n, n10, n100) {
+                (1, _, _) => PluralCategory::One,
+                (_, 2..=4, n100) if !(12..=14).contains(&n100) => PluralCategory::Few,
+                _ => PluralCategory::Many,
+            },
+            "cs" | "sk" => match n {
+                1 => PluralCategory::One,
+                2..=4 => PluralCategory::Few,
+                _ => PluralCategory::Other,
+            },
+            "he" => match n {
+                1 => PluralCategory::One,
+                2 => PluralCategory::Two,
+                _ => PluralCategory::Other,
+            },
+            "ar" => match (n, n100) {
+                (0, _) => PluralCategory::Zero,
+                (1, _) => PluralCategory::One,
+                (2, _) => PluralCategory::Two,
+                (_, 3..=10) => PluralCategory::Few,
+                (_, 11..=99) => PluralCategory::Many,
+                _ => PluralCategory::Other,
+            },
+            _ => match n {
+                1 => PluralCategory::One,
+                _ => PluralCategory::Other,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Message {
+    Text(String),
+    // Picks the variant with the plural category of the selector argument
+    Plural {
+        selector: String,
+        variants: HashMap<PluralCategory, String>,
+        default: PluralCategory,
+    },
+}
+
+// String tables of every loaded language and the current language
+#[derive(Default)]
+pub(crate) struct Localization {
+    tables: HashMap<String, HashMap<String, Message>>,
+    language: Option<String>,
+    // Bumped when the resolved strings can change, labels compare it to know when to update
+    revision: u32,
+}
+
+impl Localization {
+    // .ftl files are read as Fluent, anything else as JSON
+    pub fn load(&mut self, language: &str, path: &str) -> BreakoutResult {
+        let source = std::fs::read_to_string(path).map_err(BreakoutError::IOError)?;
+        let is_fluent = std::path::Path::new(path)
+            .extension()
+            .map_or(false, |extension| extension.eq_ignore_ascii_case("ftl"));
+        let messages = if is_fluent {
+            parse_fluent(&source)
+        } else {
+            parse_json(&source)?
+        };
+
+        self.tables
+            .entry(language.to_string())
+            .or_default()
+            .extend(messages);
+        // The first loaded language is the current one until another is set
+        if self.language.is_none() {
+            self.language = Some(language.to_string());
+        }
+        self.revision = self.revision.wrapping_add(1);
+        Ok(())
+    }
+
+    pub fn set_language(&mut self, language: &str) {
+        if !self.tables.contains_key(language) {
+            log::warn!("No strings are loaded for the language {}", language);
+        }
+        if self.language.as_deref() != Some(language) {
+            self.language = Some(language.to_string());
+            self.revision = self.revision.wrapping_add(1);
+        }
+    }
+
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    pub fn revision(&self) -> u32 {
+        self.revision
+    }
+
+    // Missing keys resolve to the key itself, so they're easy to spot on screen
+    pub fn localize<A: AsRef<str>>(&self, key: &str, args: &[(A, Argument)]) -> String {
+        let language = match &self.language {
+            Some(language) => language,
+            None => return key.to_string(),
+        };
+        let message = match self.tables.get(language).and_then(|table| table.get(key)) {
+            Some(message) => message,
+            None => return key.to_string(),
+        };
+
+        let pattern = match message {
+            Message::Text(text) => text.as_str(),
+            Message::Plural {
+                selector,
+                variants,
+                default,
+            } => {
+                let category = args
+                    .iter()
+                    .find(|(name, _)| name.as_ref() == selector)
+                    .and_then(|(_, value)| match value {
+                        Argument::Number(number) => Some(PluralCategory::of(language, *number)),
+                        Argument::Text(_) => None,
+                    })
+                    .unwrap_or(*default);
+                variants
+                    .get(&category)
+                    .or_else(|| variants.get(default))
+                    .or_else(|| variants.values().next())
+                    .map_or("", String::as_str)
+            }
+        };
+        interpolate(pattern, args)
+    }
+}
+
+// Replaces {name} and { $name } with the arguments, unknown names are kept. "{{" is a
+// literal "{"
+fn interpolate<A: AsRef<str>>(pattern: &str, args: &[(A, Argument)]) -> String {
+    let mut text = String::with_capacity(pattern.len());
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if rest.starts_with("{{") {
+            text.push('{');
+            rest = &rest[2..];
+            continue;
+        }
+        let end = match rest.find('}') {
+            Some(end) => end,
+            None => break,
+        };
+        let name = rest[1..end].trim().trim_start_matches('$');
+        match args.iter().find(|(arg, _)| arg.as_ref() == name) {
+            Some((_, value)) => text.push_str(&value.to_string()),
+            None => text.push_str(&rest[..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    text.push_str(rest);
+    text
+}
+
+// Flat or nested objects of strings, nested keys are joined with dots. Objects whose keys
+// are all plural categories are plurals selected by the "count" argument:
+// { "apples": { "one": "{count} apple", "other": "{count} apples" } }
+fn parse_json(source: &str) -> BreakoutResult<HashMap<String, Message>> {
+    let value: serde_json::Value = serde_json::from_str(source)
+        .map_err(|_| BreakoutError::GenericError("serde_json::from_str failed"))?;
+    let mut messages = HashMap::new();
+    match value {
+        serde_json::Value::Object(object) => json_messages("", &object, &mut messages)?,
+        _ => return Err(INVALID_JSON),
+    }
+    Ok(messages)
+}
+
+fn json_messages(
+    prefix: &str,
+    object: &serde_json::Map<String, serde_json::Value>,
+    messages: &mut HashMap<String, Message>,
+) -> BreakoutResult {
+    for (key, value) in object {
+        let key = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            serde_json::Value::String(text) => {
+                messages.insert(key, Message::Text(text.clone()));
+            }
+            serde_json::Value::Object(object)
+                if object
+                    .keys()
+                    .all(|name| PluralCategory::from_name(name).is_some()) =>
+            {
+                let variants = object
+                    .iter()
+                    .map(|(name, text)| {
+                        let category = PluralCategory::from_name(name).unwrap();
+                        text.as_str()
+                            .map(|text| (category, text.to_string()))
+                            .ok_or(INVALID_JSON)
+                    })
+                    .collect::<BreakoutResult<_>>()?;
+                messages.insert(
+                    key,
+                    Message::Plural {
+                        selector: String::from("count"),
+                        variants,
+                        default: PluralCategory::Other,
+                    },
+                );
+            }
+            serde_json::Value::Object(object) => json_messages(&key, object, messages)?,
+            _ => return Err(INVALID_JSON),
+        }
+    }
+    Ok(())
+}
+
+// The subset of Fluent used by games: "key = value" messages, indented continuation
+// lines, { $name } placeables and plural selectors:
+//
+// apples = { $count ->
+//     [one] One apple
+//    *[other] { $count } apples
+// }
+fn parse_fluent(source: &str) -> HashMap<String, Message> {
+    let mut messages = HashMap::new();
+    let mut lines = source.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        if line.trim().is_empty() || line.starts_with('#') || line.starts_with(char::is_whitespace)
+        {
+            continue;
+        }
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim().to_string(), value.trim()),
+            None => continue,
+        };
+
+        if let Some(selector) = value
+            .strip_prefix('{')
+            .and_then(|value| value.trim().strip_suffix("->"))
+        {
+            let selector = selector.trim().trim_start_matches('$').to_string();
+            let mut variants = HashMap::new();
+            let mut default = PluralCategory::Other;
+            for line in lines.by_ref() {
+                let line = line.trim();
+                if line.starts_with('}') {
+                    break;
+                }
+                let (is_default, line) = match line.strip_prefix('*') {
+                    Some(line) => (true, line),
+                    None => (false, line),
+                };
+                let variant = line
+                    .strip_prefix('[')
+                    .and_then(|line| line.split_once(']'))
+                    .and_then(|(name, text)| Some((PluralCategory::from_name(name.trim())?, text)));
+                if let Some((category, text)) = variant {
+                    if is_default {
+                        default = category;
+                    }
+                    variants.insert(category, text.trim().to_string());
+                }
+            }
+            messages.insert(
+                key,
+                Message::Plural {
+                    selector,
+                    variants,
+                    default,
+                },
+            );
+        } else {
+            let mut text = value.to_string();
+            // Indented lines continue the value, joined by line breaks
+            while let Some(next) = lines.peek() {
+                if !next.starts_with(char::is_whitespace) || next.trim().is_empty() {
+                    break;
+                }
+                if !text.is_empty() {
+                    text.push('\n');
+                }
+                text.push_str(next.trim());
+                lines.next();
+            }
+            messages.insert(key, Message::Text(text));
+        }
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn categories(language: &str, numbers: &[f64]) -> Vec<PluralCategory> {
+        numbers
+            .iter()
+            .map(|number| PluralCategory::of(language, *number))
+            .collect()
+    }
+
+    fn fluent(language: &str, source: &str) -> Localization {
+        let mut localization = Localization::default();
+        localization
+            .tables
+            .insert(language.to_string(), parse_fluent(source));
+        localization.set_language(language);
+        localization
+    }
+
+    #[test]
+    fn russian_plurals() {
+        use PluralCategory::*;
+        assert_eq!(
+            categories(
+                "ru",
+                &[1.0, 2.0, 4.0, 5.0, 11.0, 12.0, 14.0, 21.0, 22.0, 101.0, 111.0]
+            ),
+            vec![One, Few, Few, Many, Many, Many, Many, One, Few, One, Many]
+        );
+        assert_eq!(PluralCategory::of("ru-RU", 1.5), Other);
+    }
+
+    #[test]
+    fn polish_plurals() {
+        use PluralCategory::*;
+        assert_eq!(
+            categories(
+                "pl",
+                &[1.0, 2.0, 5.0, 11.0, 12.0, 14.0, 21.0, 22.0, 101.0, 112.0]
+            ),
+            vec![One, Few, Many, Many, Many, Many, Many, Few, Many, Many]
+        );
+    }
+
+    #[test]
+    fn arabic_plurals() {
+        use PluralCategory::*;
+        assert_eq!(
+            categories(
+                "ar",
+                &[0.0, 1.0, 2.0, 3.0, 10.0, 11.0, 99.0, 100.0, 101.0, 102.0, 103.0]
+            ),
+            vec![Zero, One, Two, Few, Few, Many, Many, Other, Other, Other, Few]
+        );
+    }
+
+    #[test]
+    fn interpolates_arguments() {
+        let args = [
+            ("name", Argument::from("Ada")),
+            ("count", Argument::from(3)),
+        ];
+        assert_eq!(
+            interpolate("{name} has {count} and { $count }", &args),
+            "Ada has 3 and 3"
+        );
+        assert_eq!(interpolate("{unknown} {name", &args), "{unknown} {name");
+    }
+
+    #[test]
+    fn double_braces_are_literal() {
+        let args = [("name", Argument::from("Ada"))];
+        assert_eq!(interpolate("{{name} {{{name}", &args), "{name} {Ada");
+        assert_eq!(interpolate("{{", &args), "{");
+    }
+
+    #[test]
+    fn parses_fluent_messages() {
+        let localization = fluent(
+            "en",
+            "# comment\n\
+             hello = Hello { $name }\n\
+             long = First line\n    second line\n\
+             \n\
+             apples = { $count ->\n    [one] One apple\n   *[other] { $count } apples\n}\n",
+        );
+        let name = [("name", Argument::from("Ada"))];
+        assert_eq!(localization.localize("hello", &name), "Hello Ada");
+        assert_eq!(
+            localization.localize::<&str>("long", &[]),
+            "First line\nsecond line"
+        );
+        assert_eq!(
+            localization.localize("apples", &[("count", Argument::from(1))]),
+            "One apple"
+        );
+        assert_eq!(
+            localization.localize("apples", &[("count", Argument::from(4))]),
+            "4 apples"
+        );
+        // Without the selector the default variant is used
+        assert_eq!(
+            localization.localize::<&str>("apples", &[]),
+            "{ $count } apples"
+        );
+        assert_eq!(localization.localize::<&str>("missing", &[]), "missing");
+    }
+
+    #[test]
+    fn fluent_plurals_use_the_language_rules() {
+        let localization = fluent(
+            "ru",
+            "files = { $count ->\n\
+             \x20   [one] { $count } файл\n\
+             \x20   [few] { $count } файла\n\
+             \x20  *[many] { $count } файлов\n\
+             }\n",
+        );
+        let localize =
+            |count: i32| localization.localize("files", &[("count", Argument::from(count))]);
+        assert_eq!(localize(21), "21 файл");
+        assert_eq!(localize(12), "12 файлов");
+        assert_eq!(localize(104), "104 файла");
+    }
+}
@@ -5,7 +5,10 @@ pub mod engine_context;
 pub mod game_context;
 pub mod game_state;
 pub mod input;
+pub mod localization;
 pub mod scene;
+pub mod sprite_sheet;
+pub mod texture_atlas;
 pub mod ui_context;
 
 mod game_window;
@@ -1,6 +1,6 @@
 use crate::{
     core::{asset_manager::AssetManager, engine_context::EngineContext, game_context::GameContext},
-    error::BreakoutResult,
+    error::{BreakoutError, BreakoutResult},
 };
 
 use super::{
@@ -51,4 +51,14 @@ pub trait Scene {
     ) -> BreakoutResult<Transition> {
         Ok(Transition::None)
     }
+
+    // Called when a frame could not be rendered, returning the error back closes the game
+    fn render_error(
+        &mut self,
+        error: BreakoutError,
+        _context: &mut GameContext,
+        _engine: &mut EngineContext,
+    ) -> BreakoutResult {
+        Err(error)
+    }
 }
@@ -0,0 +1,269 @@
+use std::{collections::HashMap, fmt, path::Path};
+
+use serde::{
+    de::{MapAccess, Visitor},
+    Deserialize, Deserializer,
+};
+
+use super::{
+    asset_manager::TextureId,
+    components::{Animation, KeyFrame},
+};
+use crate::{
+    error::{BreakoutError, BreakoutResult},
+    render::subtexture::{FrameTrim, SubTexture},
+    shapes::rectangle::Rect,
+};
+
+#[derive(Deserialize, Debug)]
+struct JsonRect {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+}
+
+#[derive(Deserialize, Debug)]
+struct JsonSize {
+    w: f32,
+    h: f32,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct JsonFrame {
+    #[serde(default)]
+    filename: String,
+    // Upright size of the frame, rotated frames take h x w pixels in the texture
+    frame: JsonRect,
+    #[serde(default)]
+    rotated: bool,
+    // Transparent borders removed by TexturePacker, spriteSourceSize places the frame back
+    #[serde(default)]
+    trimmed: bool,
+    sprite_source_size: Option<JsonRect>,
+    source_size: Option<JsonSize>,
+    // Milliseconds, only exported by Aseprite
+    duration: Option<f32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct JsonFrameTag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct JsonMeta {
+    image: String,
+    size: JsonSize,
+    #[serde(default)]
+    frame_tags: Vec<JsonFrameTag>,
+}
+
+#[derive(Deserialize, Debug)]
+struct JsonSpriteSheet {
+    #[serde(deserialize_with = "deserialize_frames")]
+    frames: Vec<JsonFrame>,
+    meta: JsonMeta,
+}
+
+// Frames are either an array or a hash keyed by name, the hash order is kept
+// because Aseprite tags refer to frames by index
+fn deserialize_frames<'de, D>(deserializer: D) -> Result<Vec<JsonFrame>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct FramesVisitor;
+
+    impl<'de> Visitor<'de> for FramesVisitor {
+        type Value = Vec<JsonFrame>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("an array or a map of frames")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: serde::de::SeqAccess<'de>,
+        {
+            let mut frames = Vec::new();
+            while let Some(frame) = seq.next_element::<JsonFrame>()? {
+                frames.push(frame);
+            }
+            Ok(frames)
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            let mut frames = Vec::new();
+            while let Some((filename, mut frame)) = map.next_entry::<String, JsonFrame>()? {
+                frame.filename = filename;
+                frames.push(frame);
+            }
+            Ok(frames)
+        }
+    }
+
+    deserializer.deserialize_any(FramesVisitor)
+}
+
+// Named frames of a TexturePacker (hash or array) or Aseprite JSON export
+pub struct SpriteSheet {
+    pub texture_id: TextureId,
+    // Frame names in file order
+    pub frame_names: Vec<String>,
+    frames: HashMap<String, SubTexture>,
+    durations: Vec<f32>,
+    tags: Vec<JsonFrameTag>,
+}
+
+impl SpriteSheet {
+    pub fn get_frame(&self, name: &str) -> Option<SubTexture> {
+        self.frames.get(name).copied()
+    }
+
+    pub fn get_frame_by_index(&self, index: usize) -> Option<SubTexture> {
+        self.frame_names
+            .get(index)
+            .and_then(|name| self.get_frame(name))
+    }
+
+    // One animation per Aseprite tag, or a single "default" animation with every frame
+    // when the sheet has no tags. Reverse and ping-pong tags are unrolled into key frames.
+    pub fn animations(&self) -> HashMap<String, Animation> {
+        let mut animations = HashMap::new();
+        if self.frame_names.is_empty() {
+            return animations;
+        }
+        if self.tags.is_empty() {
+            let frames = (0..self.frame_names.len()).collect::<Vec<_>>();
+            animations.insert("default".to_string(), self.animation(&frames));
+        }
+
+        for tag in &self.tags {
+            let forward = (tag.from..=tag.to).collect::<Vec<_>>();
+            let frames = match tag.direction.as_str() {
+                "reverse" => forward.into_iter().rev().collect(),
+                "pingpong" | "pingpong_reverse" => {
+                    // pingpong_reverse starts at the last frame and plays back to the first
+                    let there = if tag.direction == "pingpong" {
+                        forward
+                    } else {
+                        forward.into_iter().rev().collect()
+                    };
+                    let back = there
+                        .iter()
+                        .rev()
+                        .skip(1)
+                        .take(there.len().saturating_sub(2))
+                        .copied()
+                        .collect::<Vec<_>>();
+                    there.into_iter().chain(back).collect()
+                }
+                _ => forward,
+            };
+            animations.insert(tag.name.clone(), self.animation(&frames));
+        }
+
+        animations
+    }
+
+    fn animation(&self, frames: &[usize]) -> Animation {
+        let mut time = 0.0;
+        let mut key_frames = Vec::new();
+        for index in frames {
+            key_frames.push(KeyFrame {
+                texture_id: Some(self.texture_id.clone()),
+                sub_texture: self.get_frame_by_index(*index),
+                time,
+                effect: None,
+            });
+            time += self.durations[*index];
+        }
+
+        Animation {
+            length: time,
+            key_frames,
+        }
+    }
+}
+
+pub(crate) struct SpriteSheetBuilder {
+    sheet: JsonSpriteSheet,
+}
+
+impl SpriteSheetBuilder {
+    // Returns the path of the sheet image too, relative paths are resolved from the json file
+    pub(crate) fn load(path: &str) -> BreakoutResult<(String, SpriteSheetBuilder)> {
+        let json_string = std::fs::read_to_string(path).map_err(BreakoutError::IOError)?;
+        let sheet: JsonSpriteSheet = serde_json::from_str(&json_string)
+            .map_err(|_| BreakoutError::GenericError("serde_json::from_str failed"))?;
+
+        let image_path = Path::new(path)
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(&sheet.meta.image)
+            .to_string_lossy()
+            .to_string();
+
+        Ok((image_path, SpriteSheetBuilder { sheet }))
+    }
+
+    pub(crate) fn build(self, texture_id: TextureId) -> BreakoutResult<SpriteSheet> {
+        let size = &self.sheet.meta.size;
+        let mut frame_names = Vec::new();
+        let mut frames = HashMap::new();
+        let mut durations = Vec::new();
+
+        for frame in self.sheet.frames {
+            let JsonRect { x, y, w, h } = frame.frame;
+            let region = if frame.rotated {
+                Rect::new(x, y, h, w)
+            } else {
+                Rect::new(x, y, w, h)
+            };
+            let mut sub_texture = SubTexture::new(region);
+            sub_texture.rotated = frame.rotated;
+            if let (true, Some(placed), Some(source)) =
+                (frame.trimmed, frame.sprite_source_size, frame.source_size)
+            {
+                sub_texture.trim = Some(FrameTrim {
+                    offset: glam::vec2(placed.x, placed.y),
+                    source_size: glam::vec2(source.w, source.h),
+                });
+            }
+            sub_texture.texture_size = glam::vec2(size.w, size.h);
+            sub_texture.update_texture_coords();
+            frames.insert(frame.filename.clone(), sub_texture);
+            // Frames without duration play at 10 fps
+            durations.push(frame.duration.unwrap_or(100.0) / 1000.0);
+            frame_names.push(frame.filename);
+        }
+
+        let tags = self.sheet.meta.frame_tags;
+        if !frame_names.is_empty()
+            && tags
+                .iter()
+                .any(|tag| tag.from > tag.to || tag.to >= frame_names.len())
+        {
+            return Err(BreakoutError::GenericError(
+                "Sprite sheet tag is out of the frame range",
+            ));
+        }
+
+        Ok(SpriteSheet {
+            texture_id,
+            frame_names,
+            frames,
+            durations,
+            tags,
+        })
+    }
+}
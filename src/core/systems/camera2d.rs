@@ -0,0 +1,103 @@
+use rand::Rng;
+
+use crate::core::{
+    components::{Camera2D, Transform2D},
+    game_context::GameContext,
+};
+
+pub fn system_update_camera(context: &GameContext, delta: f32) {
+    let world = context.world.borrow();
+    let (render_size, window_size) = {
+        let renderer = context.renderer.borrow();
+        (renderer.display_size(), renderer.window_size())
+    };
+
+    let follows = world
+        .query::<&Camera2D>()
+        .iter()
+        .filter_map(|(id, camera)| camera.follow.as_ref().map(|follow| (id, follow.target)))
+        .collect::<Vec<_>>();
+    let targets = follows
+        .into_iter()
+        .filter_map(|(id, target)| {
+            world
+                .get::<Transform2D>(target)
+                .ok()
+                .map(|transform| (id, transform.position))
+        })
+        .collect::<Vec<_>>();
+
+    let mut rng = rand::thread_rng();
+    for (id, (camera, transform)) in world.query::<(&mut Camera2D, &mut Transform2D)>().iter() {
+        let (render_size, window_size) = camera.get_viewport_sizes(&render_size, &window_size);
+
+        if let Some(follow) = &camera.follow {
+            if let Some((_, target)) = targets.iter().find(|(camera_id, _)| *camera_id == id) {
+                let center: glam::Vec2 = camera
+                    .get_view_rect(&render_size, &window_size, &transform.position)
+                    .center()
+                    .into();
+                let distance = *target - center;
+                let distance = glam::vec2(
+                    apply_deadzone(distance.x, follow.deadzone.x),
+                    apply_deadzone(distance.y, follow.deadzone.y),
+                );
+
+                let step = if follow.smoothing > 0.0 {
+                    1.0 - (-follow.smoothing * delta).exp()
+                } else {
+                    1.0
+                };
+
+                if distance != glam::Vec2::ZERO {
+                    transform.translate(distance * step);
+                }
+            }
+        }
+
+        if let Some(bounds) = &camera.bounds {
+            let rect = camera.get_view_rect(&render_size, &window_size, &transform.position);
+            let correction = glam::vec2(
+                clamp_axis(rect.x, rect.width, bounds.x, bounds.width),
+                clamp_axis(rect.y, rect.height, bounds.y, bounds.height),
+            );
+            if correction != glam::Vec2::ZERO {
+                transform.translate(correction);
+            }
+        }
+
+        if camera.trauma > 0.0 {
+            let shake = camera.trauma * camera.trauma;
+            camera.shake_offset = camera.shake.max_offset
+                * shake
+                * glam::vec2(rng.gen_range(-1.0..1.0f32), rng.gen_range(-1.0..1.0f32));
+            camera.shake_rotation = camera.shake.max_rotation * shake * rng.gen_range(-1.0..1.0f32);
+            camera.trauma = (camera.trauma - camera.shake.decay * delta).max(0.0);
+        } else {
+            camera.shake_offset = glam::Vec2::ZERO;
+            camera.shake_rotation = 0.0;
+        }
+    }
+}
+
+fn apply_deadzone(distance: f32, deadzone: f32) -> f32 {
+    if distance > deadzone {
+        distance - deadzone
+    } else if distance < -deadzone {
+        distance + deadzone
+    } else {
+        0.0
+    }
+}
+
+fn clamp_axis(position: f32, size: f32, bounds_position: f32, bounds_size: f32) -> f32 {
+    if size >= bounds_size {
+        bounds_position + (bounds_size - size) / 2.0 - position
+    } else if position < bounds_position {
+        bounds_position - position
+    } else if position + size > bounds_position + bounds_size {
+        bounds_position + bounds_size - (position + size)
+    } else {
+        0.0
+    }
+}
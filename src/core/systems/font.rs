@@ -1,12 +1,47 @@
 use std::{cell::RefMut, rc::Rc};
 
 use crate::{
-    core::{asset_manager::AssetManager, components::Label, game_context::GameContext},
+    core::{
+        asset_manager::{AssetManager, FontId},
+        components::{Label, SpanContent, Transform2D},
+        game_context::GameContext,
+        systems::sprite::is_rich,
+    },
     error::BreakoutResult,
     font::Font,
-    render::{renderer::Renderer, texture::Texture},
+    render::{
+        renderer::Renderer,
+        texture::{Texture, TextureOptions},
+    },
 };
 
+pub fn system_update_label(context: &GameContext, delta: f32) {
+    let world = context.world.borrow();
+
+    for (_id, label) in world.query::<&mut Label>().iter() {
+        if label.markup {
+            label.time += delta;
+        }
+    }
+}
+
+// Resolves the text of the localized labels when the key, the arguments, the language or
+// the string tables changed
+pub fn system_update_localized_label(context: &GameContext, asset_manager: &AssetManager) {
+    let world = context.world.borrow();
+    let localization = asset_manager.get_localization();
+
+    for (_id, label) in world.query::<&mut Label>().iter() {
+        if label.localized_revision == Some(localization.revision()) {
+            continue;
+        }
+        if let Some(key) = &label.key {
+            label.text = localization.localize(key, &label.args);
+            label.localized_revision = Some(localization.revision());
+        }
+    }
+}
+
 pub fn system_render_font_texture(
     context: &GameContext,
     asset_manager: &mut AssetManager,
@@ -15,17 +50,69 @@ pub fn system_render_font_texture(
 ) -> BreakoutResult {
     let world = context.world.borrow();
 
-    for (_id, label) in world.query::<&Label>().iter() {
-        if let Some(font_id) = &label.font_id {
-            asset_manager.get_font_with_size(&font_id, label.size, |image| {
-                Texture::from_dynamic_image(image, renderer.device(), renderer.queue())
-            })?;
-        } else {
-            (*Rc::get_mut(&mut *default_font).unwrap()).build_with_size(label.size, |image| {
-                Texture::from_dynamic_image(image, renderer.device(), renderer.queue())
-            })?;
-        };
+    for (_id, (label, transform)) in world.query::<(&Label, Option<&Transform2D>)>().iter() {
+        // The glyphs of the cached layout are already in the atlas
+        let is_cached = transform.map_or(false, |transform| {
+            !label.needs_update(transform.position, transform.rotate, transform.scale)
+        });
+        if is_cached && !is_rich(label, asset_manager) {
+            continue;
+        }
+
+        // Image spans still need the metrics of their font
+        for span in label.spans() {
+            let (font_id, _) = label.span_font(&span.style);
+            let text = match &span.content {
+                SpanContent::Text(text) => text.as_str(),
+                SpanContent::Image(_) => "",
+            };
+            let size = span.style.size.unwrap_or(label.size);
+
+            let runs = asset_manager.font_runs(font_id, text);
+            // Fallback runs are laid out with the metrics of the font they fall back from
+            if runs
+                .iter()
+                .any(|(run_font, _)| run_font.as_ref() != font_id)
+            {
+                cache_text(asset_manager, renderer, default_font, font_id, "", size)?;
+            }
+            for (run_font, run) in runs {
+                cache_text(
+                    asset_manager,
+                    renderer,
+                    default_font,
+                    run_font.as_ref(),
+                    &run,
+                    size,
+                )?;
+            }
+        }
     }
 
     Ok(())
 }
+
+fn cache_text(
+    asset_manager: &mut AssetManager,
+    renderer: &RefMut<Renderer>,
+    default_font: &mut Rc<Font>,
+    font_id: Option<&FontId>,
+    text: &str,
+    size: u32,
+) -> BreakoutResult {
+    let get_texture = |image| {
+        Texture::from_dynamic_image_with_options(
+            image,
+            TextureOptions::mask(),
+            renderer.device(),
+            renderer.queue(),
+        )
+    };
+
+    if let Some(font_id) = font_id {
+        asset_manager.get_font_with_text(font_id, text, size, get_texture)?;
+    } else if !default_font.has_text(text, size) {
+        (*Rc::get_mut(&mut *default_font).unwrap()).cache_text(text, size, get_texture)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use std::cell::RefMut;
+
+use crate::{
+    core::{
+        components::{LightOccluder2D, PointLight2D, SpotLight2D, Transform2D},
+        game_context::GameContext,
+    },
+    render::{renderer::Renderer, RenderLight, RenderLighting},
+    shapes::rectangle::Rect,
+};
+
+pub fn system_render_lights(context: &GameContext, renderer: &mut RefMut<Renderer>) {
+    let ambient = match context.ambient_light {
+        Some(ambient) => ambient,
+        None => {
+            renderer.set_lighting(None);
+            return;
+        }
+    };
+
+    let world = context.world.borrow();
+    let mut lights = Vec::new();
+
+    for (_id, (light, transform)) in world.query::<(&PointLight2D, &Transform2D)>().iter() {
+        if !light.enabled {
+            continue;
+        }
+        lights.push(RenderLight {
+            position: transform.position,
+            color: light.color,
+            intensity: light.intensity,
+            radius: light.radius,
+            height: light.height,
+            shadow_softness: light.shadow_softness,
+            spot: None,
+        });
+    }
+
+    for (_id, (light, transform)) in world.query::<(&SpotLight2D, &Transform2D)>().iter() {
+        if !light.enabled {
+            continue;
+        }
+        let direction = glam::vec2(transform.rotate.cos(), transform.rotate.sin());
+        lights.push(RenderLight {
+            position: transform.position,
+            color: light.color,
+            intensity: light.intensity,
+            radius: light.radius,
+            height: light.height,
+            shadow_softness: light.shadow_softness,
+            spot: Some((direction, light.inner_angle, light.outer_angle)),
+        });
+    }
+
+    let occluders = world
+        .query::<(&LightOccluder2D, &Transform2D)>()
+        .iter()
+        .filter(|(_id, (occluder, _transform))| occluder.enabled)
+        .map(|(_id, (occluder, transform))| occluder_bounds(occluder.rect, transform))
+        .collect();
+
+    renderer.set_lighting(Some(&RenderLighting {
+        ambient,
+        lights,
+        occluders,
+    }));
+}
+
+// Bounding box of the rect corners moved by the transform
+fn occluder_bounds(rect: Rect, transform: &Transform2D) -> Rect {
+    let transform = glam::Affine2::from_scale_angle_translation(
+        transform.scale,
+        transform.rotate,
+        transform.position,
+    );
+    let corners = [
+        glam::vec2(rect.x, rect.y),
+        glam::vec2(rect.right(), rect.y),
+        glam::vec2(rect.right(), rect.bottom()),
+        glam::vec2(rect.x, rect.bottom()),
+    ];
+    let (min, max) = corners.iter().fold(
+        (glam::Vec2::splat(f32::MAX), glam::Vec2::splat(f32::MIN)),
+        |(min, max), corner| {
+            let corner = transform.transform_point2(*corner);
+            (min.min(corner), max.max(corner))
+        },
+    );
+    Rect::new(min.x, min.y, max.x - min.x, max.y - min.y)
+}
@@ -1,3 +1,7 @@
 pub mod animated_sprite;
+pub mod camera2d;
 pub mod font;
+pub mod light2d;
+pub mod parallax;
+pub mod particles;
 pub mod sprite;
@@ -0,0 +1,96 @@
+use crate::core::{
+    asset_manager::AssetManager,
+    components::{get_camera_views, ParallaxLayer, Sprite, SpriteMode, Transform2D},
+    game_context::GameContext,
+};
+
+pub fn system_update_parallax(context: &GameContext, asset_manager: &AssetManager) {
+    let world = context.world.borrow();
+    let (render_size, window_size) = {
+        let renderer = context.renderer.borrow();
+        (renderer.display_size(), renderer.window_size())
+    };
+
+    let camera_views = get_camera_views(&world, &render_size, &window_size);
+    if camera_views.is_empty() {
+        return;
+    }
+
+    for (_id, (layer, sprite, transform)) in world
+        .query::<(&mut ParallaxLayer, &mut Sprite, &mut Transform2D)>()
+        .iter()
+    {
+        // Layers follow the first camera drawing them
+        let view = match camera_views
+            .iter()
+            .find(|view| sprite.layer & view.layer_mask != 0)
+        {
+            Some(view) => view,
+            None => continue,
+        };
+        // The cull rect holds the shake offset, and the rotation turns around its center
+        let view_size: glam::Vec2 = view.view_rect.size().into();
+        let view_center: glam::Vec2 = view.cull_rect.center().into();
+        let view_position = view_center - view_size / 2.0;
+
+        let origin = *layer.origin.get_or_insert(transform.position);
+        let mut position = origin + view_position * (glam::Vec2::ONE - layer.factor);
+
+        if let (true, SpriteMode::NineSlice(_)) = (layer.repeat_x || layer.repeat_y, &sprite.mode) {
+            log::warn!("Nine-slice sprites can't repeat, the parallax layer is drawn once");
+            layer.repeat_x = false;
+            layer.repeat_y = false;
+        }
+
+        if layer.repeat_x || layer.repeat_y {
+            let natural_size = match (&sprite.sub_texture, &sprite.texture_id) {
+                (Some(sub_texture), _) => sub_texture.frame_size(),
+                (None, Some(texture_id)) => asset_manager.get_texture(texture_id).size().as_vec2(),
+                (None, None) => glam::Vec2::ONE,
+            };
+            let scale = transform.scale;
+            let mut size = sprite.size.unwrap_or(natural_size);
+            let mut offset = glam::Vec2::ZERO;
+
+            // Cover the whole rotated view on the repeated axes and scroll the pattern
+            // instead of the sprite
+            let cover_position: glam::Vec2 = view.cull_rect.position().into();
+            let cover_size: glam::Vec2 = view.cull_rect.size().into();
+            let anchor = if sprite.center_origin {
+                cover_size / 2.0
+            } else {
+                glam::Vec2::ZERO
+            };
+            if layer.repeat_x {
+                size.x = cover_size.x / scale.x;
+                offset.x = (cover_position.x - position.x) / scale.x;
+                position.x = cover_position.x + anchor.x;
+            }
+            if layer.repeat_y {
+                size.y = cover_size.y / scale.y;
+                offset.y = (cover_position.y - position.y) / scale.y;
+                position.y = cover_position.y + anchor.y;
+            }
+
+            if let SpriteMode::Stretch = sprite.mode {
+                sprite.mode = SpriteMode::tiled();
+            }
+            if let SpriteMode::Tiled {
+                offset: tiled_offset,
+                ..
+            } = &mut sprite.mode
+            {
+                *tiled_offset += offset - layer.applied_offset;
+                layer.applied_offset = offset;
+            }
+            if sprite.size != Some(size) {
+                sprite.size = Some(size);
+                transform.dirt = true;
+            }
+        }
+
+        if transform.position != position {
+            transform.set_position(position);
+        }
+    }
+}
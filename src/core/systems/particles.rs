@@ -0,0 +1,99 @@
+use rand::Rng;
+
+use crate::core::{
+    components::{Particle, ParticleEmitter2D, Transform2D},
+    game_context::GameContext,
+};
+
+pub fn system_update_particles(context: &GameContext, delta: f32) {
+    let world = context.world.borrow();
+    let mut rng = rand::thread_rng();
+
+    for (_id, (emitter, transform)) in world
+        .query::<(&mut ParticleEmitter2D, &Transform2D)>()
+        .iter()
+    {
+        let gravity = emitter.settings.gravity;
+        for particle in &mut emitter.particles {
+            particle.age += delta;
+            particle.velocity += gravity * delta;
+            particle.position += particle.velocity * delta;
+            particle.rotation += particle.angular_velocity * delta;
+        }
+        emitter
+            .particles
+            .retain(|particle| particle.age < particle.lifetime);
+
+        let mut count = std::mem::take(&mut emitter.pending_burst);
+        if emitter.emitting {
+            emitter.time += delta;
+
+            emitter.emit_accumulator += emitter.settings.emission_rate * delta;
+            count += emitter.emit_accumulator as usize;
+            emitter.emit_accumulator = emitter.emit_accumulator.fract();
+
+            // Bursts are expected to be sorted by time
+            while let Some(burst) = emitter.settings.bursts.get(emitter.next_burst) {
+                if burst.time > emitter.time {
+                    break;
+                }
+                count += burst.count;
+                emitter.next_burst += 1;
+            }
+
+            if emitter.time >= emitter.settings.duration {
+                if emitter.settings.looping {
+                    emitter.time -= emitter.settings.duration;
+                    emitter.next_burst = 0;
+                } else {
+                    emitter.emitting = false;
+                }
+            }
+        }
+
+        let count = count.min(
+            emitter
+                .settings
+                .max_particles
+                .saturating_sub(emitter.particles.len()),
+        );
+        let origin = if emitter.settings.world_space {
+            transform.position
+        } else {
+            glam::Vec2::ZERO
+        };
+        for _ in 0..count {
+            let particle = spawn_particle(emitter, origin, transform.rotate, &mut rng);
+            emitter.particles.push(particle);
+        }
+    }
+}
+
+fn spawn_particle(
+    emitter: &ParticleEmitter2D,
+    origin: glam::Vec2,
+    rotate: f32,
+    rng: &mut impl Rng,
+) -> Particle {
+    let settings = &emitter.settings;
+    let mut range = |(min, max): (f32, f32)| {
+        if max > min {
+            rng.gen_range(min..max)
+        } else {
+            min
+        }
+    };
+
+    let spread = range((-settings.spread, settings.spread));
+    let angle = rotate + settings.direction + spread;
+    let speed = range(settings.speed);
+
+    Particle {
+        position: origin,
+        velocity: glam::vec2(angle.cos(), angle.sin()) * speed,
+        rotation: 0.0,
+        angular_velocity: range(settings.angular_velocity),
+        age: 0.0,
+        lifetime: range(settings.lifetime).max(f32::EPSILON),
+    }
+}
@@ -1,22 +1,48 @@
 use crate::{
     core::{
-        asset_manager::AssetManager,
-        components::{Camera2D, Label, Sprite, Transform2D},
+        asset_manager::{AssetManager, FontId},
+        components::{
+            get_camera_views, label::layout_bounds, Label, ParticleEmitter2D, SpanContent, Sprite,
+            SpriteMode, SubTexture, Transform2D, ALL_LAYERS,
+        },
         game_context::GameContext,
     },
     error::BreakoutResult,
-    font::Font,
+    font::{
+        markup::{layout_rich, RichContent, RichImage, RichSpan},
+        Font,
+    },
     render::{
         renderer::Renderer,
         vertex::{
             TEXTURE_COORDS, TEXTURE_COORDS_FLIPPED_X, TEXTURE_COORDS_FLIPPED_X_Y,
             TEXTURE_COORDS_FLIPPED_Y,
         },
-        RenderText, RenderVertices,
+        RenderGlyphs, RenderInstance, RenderRichText, RenderVertices,
     },
+    shapes::rectangle::Rect,
 };
+use hecs::World;
 use std::{cell::RefMut, rc::Rc};
 
+pub fn system_update_tiled_sprite(context: &GameContext, delta: f32) {
+    let world = context.world.borrow();
+
+    for (_id, (sprite, transform)) in world.query::<(&mut Sprite, &mut Transform2D)>().iter() {
+        if let SpriteMode::Tiled {
+            offset,
+            scroll_speed,
+        } = &mut sprite.mode
+        {
+            if *scroll_speed != glam::Vec2::ZERO {
+                *offset += *scroll_speed * delta;
+                // The tiled quads are rebuilt with the vertices
+                transform.dirt = true;
+            }
+        }
+    }
+}
+
 pub fn system_render_sprite(
     context: &GameContext,
     asset_manager: &AssetManager,
@@ -27,35 +53,9 @@ pub fn system_render_sprite(
 
     renderer.clear_color(context.clear_color);
 
-    let camera_projection = if let Some((_id, (camera, transform))) =
-        world.query::<(&Camera2D, &Transform2D)>().iter().next()
-    {
-        Some(camera.get_view_matrix(
-            &renderer.display_size(),
-            &renderer.window_size(),
-            &transform.position,
-        ))
-    } else {
-        None
-    };
-
-    renderer.begin_draw(camera_projection);
     for (_id, (sprite, transform)) in world.query::<(&mut Sprite, &mut Transform2D)>().iter() {
-        if !sprite.visible {
-            continue;
-        }
-        if let Some(texture_id) = &sprite.texture_id {
+        let texture_size = if let Some(texture_id) = &sprite.texture_id {
             let texture = asset_manager.get_texture(&texture_id);
-            if transform.dirt {
-                sprite.update_vertices(
-                    transform.position,
-                    transform.rotate,
-                    transform.scale,
-                    texture.as_ref().size().as_vec2(),
-                );
-                transform.dirt = false;
-            }
-
             if let Some(sub_texture) = &mut sprite.sub_texture {
                 if sub_texture.texture_coords.is_none() {
                     sub_texture.texture_size =
@@ -63,6 +63,119 @@ pub fn system_render_sprite(
                     sub_texture.update_texture_coords()
                 }
             }
+            texture.as_ref().size().as_vec2()
+        } else {
+            glam::Vec2::ONE
+        };
+
+        if transform.dirt {
+            sprite.update_vertices(
+                transform.position,
+                transform.rotate,
+                transform.scale,
+                texture_size,
+            );
+            transform.dirt = false;
+        }
+    }
+
+    // Static labels keep their glyphs until they change or move, rich labels are laid out
+    // when drawn
+    for (_id, (label, transform)) in world.query::<(&mut Label, &Transform2D)>().iter() {
+        if is_rich(label, asset_manager)
+            || !label.needs_update(transform.position, transform.rotate, transform.scale)
+        {
+            continue;
+        }
+        let font = match &label.font_id {
+            Some(font_id) => asset_manager.get_font(font_id),
+            None => default_font,
+        };
+        label.update_vertices(font, transform.position, transform.rotate, transform.scale);
+    }
+
+    for (_id, emitter) in world.query::<&mut ParticleEmitter2D>().iter() {
+        if let (Some(texture_id), Some(sub_texture)) =
+            (&emitter.texture_id, &mut emitter.sub_texture)
+        {
+            if sub_texture.texture_coords.is_none() {
+                let texture = asset_manager.get_texture(&texture_id);
+                sub_texture.texture_size = glam::vec2(texture.width as f32, texture.height as f32);
+                sub_texture.update_texture_coords()
+            }
+        }
+    }
+
+    let camera_views = get_camera_views(&world, &renderer.display_size(), &renderer.window_size());
+
+    renderer.begin_draw();
+    if camera_views.is_empty() {
+        let display_size = renderer.display_size().as_vec2();
+        renderer.begin_view(None, None);
+        draw_world(
+            &world,
+            asset_manager,
+            renderer,
+            default_font,
+            ALL_LAYERS,
+            &Rect::new(0.0, 0.0, display_size.x, display_size.y),
+        );
+    } else {
+        for camera_view in &camera_views {
+            renderer.begin_view(Some(camera_view.projection), Some(camera_view.viewport));
+            draw_world(
+                &world,
+                asset_manager,
+                renderer,
+                default_font,
+                camera_view.layer_mask,
+                &camera_view.cull_rect,
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn draw_world(
+    world: &World,
+    asset_manager: &AssetManager,
+    renderer: &mut RefMut<Renderer>,
+    default_font: &Rc<Font>,
+    layer_mask: u32,
+    cull_rect: &Rect,
+) {
+    for (_id, sprite) in world.query::<&Sprite>().iter() {
+        if !sprite.visible || sprite.layer & layer_mask == 0 {
+            continue;
+        }
+        if !cull_rect.intersects(&sprite.cache.bounds) {
+            continue;
+        }
+        let normal_texture = sprite
+            .normal_map
+            .as_ref()
+            .map(|normal_map| asset_manager.get_texture(normal_map));
+
+        if !sprite.cache.quads.is_empty() {
+            let texture = sprite
+                .texture_id
+                .as_ref()
+                .map(|texture_id| asset_manager.get_texture(texture_id).clone());
+            for quad in &sprite.cache.quads {
+                renderer.draw_vertices(RenderVertices {
+                    texture: texture.clone(),
+                    normal_texture: normal_texture.cloned(),
+                    vertices: quad.vertices,
+                    color: sprite.color.unwrap_or(glam::vec4(1.0, 1.0, 1.0, 1.0)),
+                    texture_coords: quad.texture_coords,
+                    lit: sprite.lit,
+                    wrap: quad.wrap,
+                    sdf: None,
+                });
+            }
+        } else if let Some(texture_id) = &sprite.texture_id {
+            let texture = asset_manager.get_texture(&texture_id);
 
             let texture_coords = if let Some(sub_texture) = &sprite.sub_texture {
                 sub_texture.texture_coords.as_ref().unwrap()
@@ -75,34 +188,38 @@ pub fn system_render_sprite(
                 }
             };
 
-            renderer.draw_vertices(RenderVertices {
+            renderer.draw_instance(RenderInstance {
                 texture: Some(texture.clone()),
-                vertices: sprite.get_vertices().clone(),
+                normal_texture: normal_texture.cloned(),
+                transform: sprite.cache.transform,
                 color: sprite.color.unwrap_or(glam::vec4(1.0, 1.0, 1.0, 1.0)),
                 texture_coords: texture_coords.clone(),
+                lit: sprite.lit,
+                wrap: false,
             });
         } else {
-            if transform.dirt {
-                sprite.update_vertices(
-                    transform.position,
-                    transform.rotate,
-                    transform.scale,
-                    glam::Vec2::ONE,
-                );
-                transform.dirt = false;
-            }
-            renderer.draw_vertices(RenderVertices {
+            renderer.draw_instance(RenderInstance {
                 texture: None,
-                vertices: sprite.get_vertices().clone(),
+                normal_texture: normal_texture.cloned(),
+                transform: sprite.cache.transform,
                 color: sprite.color.unwrap_or(glam::vec4(1.0, 1.0, 1.0, 1.0)),
                 texture_coords: TEXTURE_COORDS.clone(),
+                lit: sprite.lit,
+                wrap: false,
             });
         };
     }
 
+    for (_id, (emitter, transform)) in world.query::<(&ParticleEmitter2D, &Transform2D)>().iter() {
+        if emitter.layer & layer_mask == 0 {
+            continue;
+        }
+        draw_particles(renderer, asset_manager, emitter, transform, cull_rect);
+    }
+
     // TODO label should not be here
-    for (_id, (label, _transform)) in world.query::<(&mut Label, &Transform2D)>().iter() {
-        if !label.visible {
+    for (_id, (label, transform)) in world.query::<(&Label, &Transform2D)>().iter() {
+        if !label.visible || label.layer & layer_mask == 0 {
             continue;
         }
 
@@ -112,16 +229,172 @@ pub fn system_render_sprite(
             default_font
         };
 
-        renderer.draw_text(RenderText {
-            text: &label.text,
-            font: font.clone(),
+        if is_rich(label, asset_manager) {
+            let spans = rich_spans(label, asset_manager, default_font);
+            let layout = layout_rich(&spans, &label.layout_options());
+            let bounds = layout_bounds(
+                layout.size,
+                glam::Affine2::from_scale_angle_translation(
+                    transform.scale,
+                    transform.rotate,
+                    transform.position,
+                ),
+            );
+            if cull_rect.intersects(&bounds) {
+                renderer.draw_rich_text(RenderRichText {
+                    spans: &spans,
+                    layout: &layout,
+                    position: transform.position,
+                    scale: transform.scale,
+                    rotate: transform.rotate,
+                    effects: label.effects,
+                    time: label.time,
+                });
+            }
+            continue;
+        }
+
+        if !cull_rect.intersects(&label.cache.bounds) {
+            continue;
+        }
+
+        renderer.draw_glyphs(RenderGlyphs {
+            glyphs: &label.cache.glyphs,
+            font,
             size: label.size,
-            position: _transform.position,
-            scale: _transform.scale,
             color: label.color.unwrap_or(glam::vec4(1.0, 1.0, 1.0, 1.0)),
+            effects: label.effects,
         });
     }
-    renderer.end_draw();
+}
 
-    Ok(())
+// Markup and fonts with fallbacks are drawn as one span per style or font
+pub(crate) fn is_rich(label: &Label, asset_manager: &AssetManager) -> bool {
+    label.markup
+        || label.font_id.as_ref().map_or(false, |font_id| {
+            !asset_manager.get_font_fallbacks(font_id).is_empty()
+        })
+}
+
+// Resolves the fonts and images of the markup spans, unknown images are skipped
+fn rich_spans(
+    label: &Label,
+    asset_manager: &AssetManager,
+    default_font: &Rc<Font>,
+) -> Vec<RichSpan> {
+    let color = label.color.unwrap_or(glam::vec4(1.0, 1.0, 1.0, 1.0));
+    let mut spans = Vec::new();
+
+    for span in label.spans() {
+        let (font_id, bold) = label.span_font(&span.style);
+        let get_font = |font_id: Option<&FontId>| {
+            font_id.map_or_else(
+                || default_font.clone(),
+                |id| asset_manager.get_font(id).clone(),
+            )
+        };
+        let rich_span = |content, font, primary| RichSpan {
+            content,
+            font,
+            primary,
+            size: span.style.size.unwrap_or(label.size),
+            color: span.style.color.unwrap_or(color),
+            bold,
+            animation: span.style.animation,
+        };
+
+        let content = match span.content {
+            SpanContent::Text(ref text) => {
+                for (run_font, run) in asset_manager.font_runs(font_id, text) {
+                    let primary = Some(get_font(font_id)).filter(|_| run_font.as_ref() != font_id);
+                    spans.push(rich_span(
+                        RichContent::Text(run),
+                        get_font(run_font.as_ref()),
+                        primary,
+                    ));
+                }
+                continue;
+            }
+            SpanContent::Image(ref name) => match label.images.get(name) {
+                Some((texture_id, sub_texture)) => {
+                    let texture = asset_manager.get_texture(texture_id);
+                    let sub_texture = sub_texture.unwrap_or_else(|| {
+                        let region =
+                            Rect::new(0.0, 0.0, texture.width as f32, texture.height as f32);
+                        SubTexture::from_texture(region, texture)
+                    });
+                    let region = sub_texture.region;
+                    RichContent::Image(RichImage {
+                        texture: texture.clone(),
+                        texture_coords: sub_texture.texture_coords.unwrap_or(TEXTURE_COORDS),
+                        aspect: region.width / region.height.max(1.0),
+                    })
+                }
+                None => {
+                    log::warn!("Label image {} is not registered", name);
+                    continue;
+                }
+            },
+        };
+
+        spans.push(rich_span(content, get_font(font_id), None));
+    }
+    spans
+}
+
+fn draw_particles(
+    renderer: &mut RefMut<Renderer>,
+    asset_manager: &AssetManager,
+    emitter: &ParticleEmitter2D,
+    transform: &Transform2D,
+    cull_rect: &Rect,
+) {
+    let settings = &emitter.settings;
+    let texture = emitter
+        .texture_id
+        .as_ref()
+        .map(|texture_id| asset_manager.get_texture(texture_id).clone());
+    let texture_coords = emitter
+        .sub_texture
+        .as_ref()
+        .and_then(|sub_texture| sub_texture.texture_coords)
+        .unwrap_or(TEXTURE_COORDS);
+    let offset = if settings.world_space {
+        glam::Vec2::ZERO
+    } else {
+        transform.position
+    };
+
+    for particle in &emitter.particles {
+        let life = particle.age / particle.lifetime;
+        let size = settings.size * settings.scale.sample(life, 1.0);
+        let position = particle.position + offset;
+
+        // Conservative bounds that hold for any rotation
+        let extent = size.length() * 0.5;
+        let bounds = Rect::new(
+            position.x - extent,
+            position.y - extent,
+            extent * 2.0,
+            extent * 2.0,
+        );
+        if !cull_rect.intersects(&bounds) {
+            continue;
+        }
+
+        // The unit quad is centered on the particle
+        let transform =
+            glam::Affine2::from_scale_angle_translation(size, particle.rotation, position)
+                * glam::Affine2::from_translation(glam::vec2(-0.5, -0.5));
+
+        renderer.draw_instance(RenderInstance {
+            texture: texture.clone(),
+            normal_texture: None,
+            transform,
+            color: settings.color.sample(life, glam::Vec4::ONE),
+            texture_coords,
+            lit: emitter.lit,
+            wrap: false,
+        });
+    }
 }
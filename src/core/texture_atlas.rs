@@ -0,0 +1,210 @@
+use std::{collections::HashMap, path::Path};
+
+use image::{DynamicImage, GenericImage, GenericImageView, RgbaImage};
+
+use super::asset_manager::TextureId;
+use crate::{
+    error::{BreakoutError, BreakoutResult},
+    render::{subtexture::SubTexture, texture::TextureOptions},
+    shapes::rectangle::Rect,
+};
+
+// Collects images to be packed into atlas pages by AssetManager::build_atlas
+pub struct TextureAtlasBuilder {
+    pub(crate) page_size: u32,
+    // Empty pixels between two images
+    pub(crate) padding: u32,
+    // Edge pixels repeated around each image so filtering doesn't bleed in the neighbours
+    pub(crate) extrude: u32,
+    // Sampler and mipmap options of the pages
+    pub(crate) options: TextureOptions,
+    pub(crate) images: Vec<(String, RgbaImage)>,
+}
+
+impl Default for TextureAtlasBuilder {
+    fn default() -> Self {
+        Self {
+            page_size: 2048,
+            padding: 2,
+            extrude: 1,
+            options: TextureOptions::default(),
+            images: Vec::new(),
+        }
+    }
+}
+
+impl TextureAtlasBuilder {
+    pub fn new() -> TextureAtlasBuilder {
+        TextureAtlasBuilder::default()
+    }
+
+    pub fn with_page_size(mut self, page_size: u32) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn with_extrude(mut self, extrude: u32) -> Self {
+        self.extrude = extrude;
+        self
+    }
+
+    pub fn with_options(mut self, options: TextureOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    // An image added again under the same name replaces the previous one
+    pub fn add_image(&mut self, name: &str, image: DynamicImage) {
+        let image = image.into_rgba8();
+        match self.images.iter_mut().find(|(added, _)| added == name) {
+            Some(added) => {
+                log::warn!("Atlas image {} was added twice, the last one is kept", name);
+                added.1 = image;
+            }
+            None => self.images.push((name.to_string(), image)),
+        }
+    }
+
+    // The image is named after the file name without extension
+    pub fn add_file(&mut self, path: &str) -> BreakoutResult {
+        let image = image::open(path).map_err(BreakoutError::ImageError)?;
+        self.add_image(&image_name(Path::new(path)), image);
+        Ok(())
+    }
+
+    // Adds every image in the directory, not recursive
+    pub fn add_directory(&mut self, path: &str) -> BreakoutResult {
+        let mut paths = std::fs::read_dir(path)
+            .map_err(BreakoutError::IOError)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && image::ImageFormat::from_path(path).is_ok())
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        for path in paths {
+            let image = image::open(&path).map_err(BreakoutError::ImageError)?;
+            self.add_image(&image_name(&path), image);
+        }
+        Ok(())
+    }
+
+    // Shelf packing, images are sorted by height and placed left to right in rows
+    pub(crate) fn pack(&self) -> BreakoutResult<Vec<AtlasPage>> {
+        let border = self.extrude * 2 + self.padding;
+        let mut order = (0..self.images.len()).collect::<Vec<_>>();
+        order.sort_by_key(|i| std::cmp::Reverse(self.images[*i].1.height()));
+
+        let mut pages: Vec<AtlasPage> = Vec::new();
+        let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+        for i in order {
+            let (name, image) = &self.images[i];
+            let (width, height) = (image.width() + border, image.height() + border);
+            if width > self.page_size || height > self.page_size {
+                return Err(BreakoutError::GenericError(
+                    "Image is bigger than the atlas page size",
+                ));
+            }
+
+            if x + width > self.page_size {
+                x = 0;
+                y += shelf_height;
+                shelf_height = 0;
+            }
+            if pages.is_empty() || y + height > self.page_size {
+                pages.push(AtlasPage {
+                    image: RgbaImage::new(self.page_size, self.page_size),
+                    used_height: 0,
+                    regions: Vec::new(),
+                });
+                x = 0;
+                y = 0;
+                shelf_height = 0;
+            }
+
+            let page = pages.last_mut().unwrap();
+            let (inner_x, inner_y) = (x + self.extrude, y + self.extrude);
+            page.image
+                .copy_from(image, inner_x, inner_y)
+                .map_err(BreakoutError::ImageError)?;
+            extrude(&mut page.image, inner_x, inner_y, image, self.extrude);
+            page.regions.push((
+                name.clone(),
+                Rect::new(
+                    inner_x as f32,
+                    inner_y as f32,
+                    image.width() as f32,
+                    image.height() as f32,
+                ),
+            ));
+            page.used_height = page.used_height.max(y + height);
+
+            x += width;
+            shelf_height = shelf_height.max(height);
+        }
+
+        // The last page only needs to be as tall as its content
+        if let Some(page) = pages.last_mut() {
+            let height = page.used_height.max(1);
+            page.image = page.image.view(0, 0, self.page_size, height).to_image();
+        }
+
+        Ok(pages)
+    }
+}
+
+pub(crate) struct AtlasPage {
+    pub image: RgbaImage,
+    pub used_height: u32,
+    pub regions: Vec<(String, Rect)>,
+}
+
+fn image_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+// Repeats the border pixels of the image placed at x, y outwards
+fn extrude(page: &mut RgbaImage, x: u32, y: u32, image: &RgbaImage, extrude: u32) {
+    let (width, height) = image.dimensions();
+    if extrude == 0 || width == 0 || height == 0 {
+        return;
+    }
+
+    let extruded = extrude as i64;
+    for dy in -extruded..height as i64 + extruded {
+        for dx in -extruded..width as i64 + extruded {
+            if dx >= 0 && dy >= 0 && dx < width as i64 && dy < height as i64 {
+                continue;
+            }
+            let source_x = dx.clamp(0, width as i64 - 1) as u32;
+            let source_y = dy.clamp(0, height as i64 - 1) as u32;
+            page.put_pixel(
+                (x as i64 + dx) as u32,
+                (y as i64 + dy) as u32,
+                *image.get_pixel(source_x, source_y),
+            );
+        }
+    }
+}
+
+// Named regions of the packed atlas pages
+pub struct TextureAtlas {
+    pub pages: Vec<TextureId>,
+    pub(crate) regions: HashMap<String, (TextureId, SubTexture)>,
+}
+
+impl TextureAtlas {
+    pub fn get(&self, name: &str) -> Option<(TextureId, SubTexture)> {
+        self.regions.get(name).cloned()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.regions.keys()
+    }
+}
@@ -5,12 +5,34 @@ use std::{
     rc::Rc,
 };
 
+use winit::event::{ElementState, ModifiersState, VirtualKeyCode, WindowEvent};
+
 use super::engine::EngineTimerView;
-use crate::{error::BreakoutResult, font::Font, gui::group::Group, render::renderer::Renderer};
+use crate::{
+    core::asset_manager::AssetManager,
+    error::{BreakoutError, BreakoutResult},
+    font::Font,
+    gui::{
+        group::Group,
+        text_input::{Clipboard, TextEdit},
+        TEXT_SIZE,
+    },
+    render::{
+        renderer::Renderer,
+        texture::{Texture, TextureOptions},
+    },
+};
 
 pub struct UIContext {
     build: HashMap<String, Group>,
     default_font: Rc<Font>,
+    // Keyboard input for the focused text input, applied when the groups are built
+    edits: Vec<TextEdit>,
+    // A text input had the focus in the last built frame
+    text_focused: bool,
+    focused_this_frame: bool,
+    modifiers: ModifiersState,
+    clipboard: Clipboard,
 }
 
 impl UIContext {
@@ -22,31 +44,109 @@ impl UIContext {
         Ok(UIContext {
             build,
             default_font,
+            edits: Vec::new(),
+            text_focused: false,
+            focused_this_frame: false,
+            modifiers: ModifiersState::empty(),
+            clipboard: Clipboard::new(),
         })
     }
 
-    pub(crate) fn on_event(&mut self, _event: &winit::event::WindowEvent) -> bool {
-        false
+    // Captures the keyboard while a text input has the focus, except for enter, escape and
+    // tab so the scene can submit or leave the field
+    pub(crate) fn on_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = *modifiers;
+                false
+            }
+            WindowEvent::ReceivedCharacter(c) if self.text_focused => {
+                // Shortcuts and editing keys also send control characters
+                if !c.is_control() && !self.modifiers.ctrl() && !self.modifiers.logo() {
+                    self.edits.push(TextEdit::Insert(c.to_string()));
+                }
+                true
+            }
+            WindowEvent::KeyboardInput { input, .. } if self.text_focused => {
+                let key_code = match input.virtual_keycode {
+                    Some(VirtualKeyCode::Return | VirtualKeyCode::Escape | VirtualKeyCode::Tab) => {
+                        return false
+                    }
+                    Some(key_code) => key_code,
+                    None => return true,
+                };
+                if input.state == ElementState::Pressed {
+                    if let Some(edit) = self.key_edit(key_code) {
+                        self.edits.push(edit);
+                    }
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn key_edit(&mut self, key_code: VirtualKeyCode) -> Option<TextEdit> {
+        let select = self.modifiers.shift();
+        let shortcut = self.modifiers.ctrl() || self.modifiers.logo();
+        let edit = match key_code {
+            VirtualKeyCode::Back => TextEdit::Backspace,
+            VirtualKeyCode::Delete => TextEdit::Delete,
+            VirtualKeyCode::Left => TextEdit::Left { select },
+            VirtualKeyCode::Right => TextEdit::Right { select },
+            VirtualKeyCode::Home => TextEdit::Home { select },
+            VirtualKeyCode::End => TextEdit::End { select },
+            VirtualKeyCode::A if shortcut => TextEdit::SelectAll,
+            VirtualKeyCode::C if shortcut => TextEdit::Copy,
+            VirtualKeyCode::X if shortcut => TextEdit::Cut,
+            VirtualKeyCode::V if shortcut => TextEdit::Paste(self.clipboard.get_text()),
+            _ => return None,
+        };
+        Some(edit)
     }
 
-    pub(crate) fn render(&mut self, renderer: &mut RefMut<Renderer>, view_time: &EngineTimerView) {
-        // TODO WGPU pending
-        // self.default_font
-        //     .build_with_size(25, |image| Ok(renderer.generate_texture(image)?))
-        //     .unwrap();
+    pub(crate) fn end_frame(&mut self) {
+        self.text_focused = std::mem::take(&mut self.focused_this_frame);
+        self.edits.clear();
+    }
 
-        // TODO: Set UI Camera later
-        // TODO WGPU pending
-        // renderer.begin_draw(None);
+    pub(crate) fn render(
+        &mut self,
+        renderer: &mut RefMut<Renderer>,
+        asset_manager: &AssetManager,
+        view_time: &EngineTimerView,
+    ) -> BreakoutResult {
+        let texts = self
+            .build
+            .values()
+            .flat_map(|build| build.texts(asset_manager))
+            .filter(|text| !self.default_font.has_text(text, TEXT_SIZE))
+            .collect::<Vec<_>>();
+        if !texts.is_empty() {
+            // The font is only shared with the groups while they draw
+            let font = Rc::get_mut(&mut self.default_font).ok_or(BreakoutError::GenericError(
+                "The gui font is still in use and its glyphs can't be cached",
+            ))?;
+            for text in texts {
+                font.cache_text(&text, TEXT_SIZE, |image| {
+                    Texture::from_dynamic_image_with_options(
+                        image,
+                        TextureOptions::mask(),
+                        renderer.device(),
+                        renderer.queue(),
+                    )
+                })?;
+            }
+        }
 
+        // Drawn over the world in window pixels
+        renderer.begin_view(None, None);
         for (_, build) in &self.build {
-            build.render(renderer, view_time, &self.default_font);
+            build.render(renderer, asset_manager, view_time, &self.default_font);
         }
 
-        // TODO WGPU pending
-        // renderer.end_draw();
-
         self.build.clear();
+        Ok(())
     }
 
     pub fn begin<F>(&mut self, title: &str, mut f: F)
@@ -63,7 +163,14 @@ impl UIContext {
             Group::new()
         };
 
+        group.edits = std::mem::take(&mut self.edits);
         f(&mut group);
+        // Edits no input of the group took are left for the next group
+        self.edits = std::mem::take(&mut group.edits);
+        self.focused_this_frame |= std::mem::take(&mut group.has_focus);
+        if let Some(copied) = group.copied.take() {
+            self.clipboard.set_text(copied);
+        }
         self.build.insert(String::from(title), group);
     }
 }
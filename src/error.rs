@@ -7,6 +7,8 @@ pub enum BreakoutError {
     FontError(freetype::Error),
     GenericError(&'static str),
     RenderError(&'static str),
+    SurfaceError(wgpu::SurfaceError),
+    DeviceError(wgpu::Error),
 
     ImageError(ImageError),
     IOError(std::io::Error),
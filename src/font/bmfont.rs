@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+
+use crate::error::{BreakoutError, BreakoutResult};
+
+const INVALID_FILE: BreakoutError = BreakoutError::GenericError("Invalid BMFont file");
+
+pub(crate) struct BitmapChar {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    // From the top left corner of the line to the top left corner of the glyph
+    pub x_offset: i32,
+    pub y_offset: i32,
+    pub x_advance: i32,
+    pub page: usize,
+}
+
+// An AngelCode BMFont descriptor, the glyphs are already packed in the page images
+#[derive(Default)]
+pub(crate) struct BitmapFont {
+    // Size the glyphs were drawn at
+    pub size: u32,
+    pub line_height: u32,
+    // Distance from the top of the line to the baseline
+    pub base: u32,
+    // Image files, relative to the descriptor
+    pub pages: Vec<String>,
+    pub chars: HashMap<u32, BitmapChar>,
+    pub kernings: HashMap<(u32, u32), i32>,
+}
+
+impl BitmapFont {
+    // Reads the text, XML and binary versions of the format
+    pub fn parse(bytes: &[u8]) -> BreakoutResult<BitmapFont> {
+        if bytes.starts_with(b"BMF") {
+            return BitmapFont::parse_binary(bytes);
+        }
+        let text = std::str::from_utf8(bytes).map_err(|_| INVALID_FILE)?;
+        if text.trim_start().starts_with('<') {
+            // The XML elements have the same names and attributes as the lines of the text
+            // version
+            let elements = text
+                .split('<')
+                .filter_map(|element| element.split('>').next())
+                .map(|element| element.trim_end_matches('/'))
+                .filter(|element| !element.starts_with('?') && !element.starts_with('!'));
+            BitmapFont::parse_tags(elements)
+        } else {
+            BitmapFont::parse_tags(text.lines())
+        }
+    }
+
+    fn parse_tags<'a>(tags: impl Iterator<Item = &'a str>) -> BreakoutResult<BitmapFont> {
+        let mut font = BitmapFont::default();
+
+        for tag in tags {
+            let (name, attributes) = match tag.trim().split_once(char::is_whitespace) {
+                Some((name, attributes)) => (name, parse_attributes(attributes)),
+                None => continue,
+            };
+            let number = |key: &str| -> BreakoutResult<i32> {
+                attributes
+                    .get(key)
+                    .and_then(|value| value.parse().ok())
+                    .ok_or(INVALID_FILE)
+            };
+
+            match name {
+                "info" => font.size = number("size")?.unsigned_abs(),
+                "common" => {
+                    font.line_height = number("lineHeight")? as u32;
+                    font.base = number("base")? as u32;
+                }
+                "page" => {
+                    let id = number("id")? as usize;
+                    let file = attributes.get("file").ok_or(INVALID_FILE)?;
+                    if font.pages.len() <= id {
+                        font.pages.resize(id + 1, String::new());
+                    }
+                    font.pages[id] = file.to_string();
+                }
+                "char" => {
+                    font.chars.insert(
+                        number("id")? as u32,
+                        BitmapChar {
+                            x: number("x")? as u32,
+                            y: number("y")? as u32,
+                            width: number("width")? as u32,
+                            height: number("height")? as u32,
+                            x_offset: number("xoffset")?,
+                            y_offset: number("yoffset")?,
+                            x_advance: number("xadvance")?,
+                            page: number("page")? as usize,
+                        },
+                    );
+                }
+                "kerning" => {
+                    let pair = (number("first")? as u32, number("second")? as u32);
+                    font.kernings.insert(pair, number("amount")?);
+                }
+                _ => {}
+            }
+        }
+
+        font.validate()
+    }
+
+    fn parse_binary(bytes: &[u8]) -> BreakoutResult<BitmapFont> {
+        if bytes.get(3) != Some(&3) {
+            return Err(BreakoutError::GenericError(
+                "Only version 3 of the binary BMFont format is supported",
+            ));
+        }
+        let mut font = BitmapFont::default();
+
+        let mut offset = 4;
+        while offset < bytes.len() {
+            let block_type = bytes[offset];
+            let size = read_u32(bytes, offset + 1)? as usize;
+            let block = bytes
+                .get(offset + 5..offset + 5 + size)
+                .ok_or(INVALID_FILE)?;
+            offset += 5 + size;
+
+            match block_type {
+                1 => font.size = (read_u16(block, 0)? as i16).unsigned_abs() as u32,
+                2 => {
+                    font.line_height = read_u16(block, 0)? as u32;
+                    font.base = read_u16(block, 2)? as u32;
+                }
+                // Null terminated file names
+                3 => {
+                    font.pages = block
+                        .split(|byte| *byte == 0)
+                        .filter(|name| !name.is_empty())
+                        .map(|name| String::from_utf8_lossy(name).into_owned())
+                        .collect();
+                }
+                4 => {
+                    for entry in block.chunks_exact(20) {
+                        font.chars.insert(
+                            read_u32(entry, 0)?,
+                            BitmapChar {
+                                x: read_u16(entry, 4)? as u32,
+                                y: read_u16(entry, 6)? as u32,
+                                width: read_u16(entry, 8)? as u32,
+                                height: read_u16(entry, 10)? as u32,
+                                x_offset: read_u16(entry, 12)? as i16 as i32,
+                                y_offset: read_u16(entry, 14)? as i16 as i32,
+                                x_advance: read_u16(entry, 16)? as i16 as i32,
+                                page: entry[18] as usize,
+                            },
+                        );
+                    }
+                }
+                5 => {
+                    for kerning in block.chunks_exact(10) {
+                        let pair = (read_u32(kerning, 0)?, read_u32(kerning, 4)?);
+                        font.kernings
+                            .insert(pair, read_u16(kerning, 8)? as i16 as i32);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        font.validate()
+    }
+
+    fn validate(mut self) -> BreakoutResult<BitmapFont> {
+        if self.chars.is_empty() || self.pages.is_empty() || self.line_height == 0 {
+            return Err(INVALID_FILE);
+        }
+        if self
+            .chars
+            .values()
+            .any(|entry| entry.page >= self.pages.len())
+        {
+            return Err(INVALID_FILE);
+        }
+        // The size is negative when it matches the height of the characters, and can be left out
+        if self.size == 0 {
+            self.size = self.line_height;
+        }
+        Ok(self)
+    }
+}
+
+// key=value pairs, the values can be quoted and contain spaces
+fn parse_attributes(text: &str) -> HashMap<&str, &str> {
+    let mut attributes = HashMap::new();
+    let mut rest = text.trim_start();
+    while let Some(equal) = rest.find('=') {
+        let key = rest[..equal].trim();
+        rest = &rest[equal + 1..];
+        let value = if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            rest = quoted.get(end + 1..).unwrap_or("");
+            &quoted[..end]
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let value = &rest[..end];
+            rest = &rest[end..];
+            value
+        };
+        attributes.insert(key, value);
+        rest = rest.trim_start();
+    }
+    attributes
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> BreakoutResult<u16> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .ok_or(INVALID_FILE)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> BreakoutResult<u32> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or(INVALID_FILE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(block_type: u8, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![block_type];
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    // A binary font with a single "A" on page.png
+    fn binary_font() -> Vec<u8> {
+        let mut bytes = b"BMF\x03".to_vec();
+        bytes.extend(block(1, &[32, 0]));
+        bytes.extend(block(2, &[40, 0, 30, 0]));
+        bytes.extend(block(3, b"page.png\0"));
+        let mut char_a = Vec::new();
+        char_a.extend_from_slice(&65u32.to_le_bytes());
+        for value in [1u16, 2, 10, 20, 0xffff, 3, 11] {
+            char_a.extend_from_slice(&value.to_le_bytes());
+        }
+        char_a.extend_from_slice(&[0, 15]);
+        bytes.extend(block(4, &char_a));
+        bytes
+    }
+
+    #[test]
+    fn parses_binary_fonts() {
+        let font = BitmapFont::parse(&binary_font()).unwrap();
+        assert_eq!(font.size, 32);
+        assert_eq!((font.line_height, font.base), (40, 30));
+        assert_eq!(font.pages, vec!["page.png".to_string()]);
+        let char_a = &font.chars[&65];
+        assert_eq!(
+            (char_a.x, char_a.y, char_a.width, char_a.height),
+            (1, 2, 10, 20)
+        );
+        assert_eq!(
+            (char_a.x_offset, char_a.y_offset, char_a.x_advance),
+            (-1, 3, 11)
+        );
+    }
+
+    #[test]
+    fn truncated_binary_blocks_are_invalid() {
+        let bytes = binary_font();
+        // Cut in the header of a block and in the middle of its data
+        for len in [4, 5, 8, bytes.len() - 1] {
+            assert!(
+                BitmapFont::parse(&bytes[..len]).is_err(),
+                "{} bytes were parsed",
+                len
+            );
+        }
+
+        // The size of the block goes past the end of the file
+        let mut bytes = b"BMF\x03".to_vec();
+        bytes.extend(&[2, 0xff, 0xff, 0xff, 0xff, 40, 0, 30, 0]);
+        assert!(BitmapFont::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn short_fields_in_a_binary_block_are_invalid() {
+        let mut bytes = b"BMF\x03".to_vec();
+        bytes.extend(block(2, &[40, 0, 30]));
+        assert!(BitmapFont::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn other_binary_versions_are_rejected() {
+        let mut bytes = binary_font();
+        bytes[3] = 2;
+        assert!(BitmapFont::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn parses_text_fonts() {
+        let text = "info face=\"Some Font\" size=-24\n\
+                    common lineHeight=28 base=22\n\
+                    page id=0 file=\"font page.png\"\n\
+                    char id=66 x=3 y=4 width=12 height=16 xoffset=1 yoffset=6 xadvance=13 page=0\n\
+                    kerning first=65 second=66 amount=-2\n";
+        let font = BitmapFont::parse(text.as_bytes()).unwrap();
+        assert_eq!(font.size, 24);
+        assert_eq!(font.pages, vec!["font page.png".to_string()]);
+        assert_eq!(font.chars[&66].x_advance, 13);
+        assert_eq!(font.kernings[&(65, 66)], -2);
+    }
+}
@@ -0,0 +1,369 @@
+use super::{
+    shaping::{reorder_line, ShapedGlyph},
+    Font,
+};
+
+// Appended to truncated lines, always part of the cached ASCII glyphs
+pub(crate) const ELLIPSIS: &str = "...";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+    // Stretches the spaces so the lines fill the max width, the last line of a paragraph
+    // is left aligned
+    Justify,
+}
+
+impl Default for TextAlign {
+    fn default() -> Self {
+        TextAlign::Left
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerticalAlign {
+    Top,
+    Middle,
+    Bottom,
+}
+
+impl Default for VerticalAlign {
+    fn default() -> Self {
+        VerticalAlign::Top
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextLayoutOptions {
+    // Lines are wrapped or truncated to this width
+    pub max_width: Option<f32>,
+    // Height of the box used by the vertical alignment, lines that don't fit are dropped
+    pub max_height: Option<f32>,
+    pub align: TextAlign,
+    pub vertical_align: VerticalAlign,
+    // Multiplier of the font line spacing
+    pub line_height: f32,
+    // Pixels added between two glyphs
+    pub letter_spacing: f32,
+    // Breaks the lines between words, or inside the words longer than max_width
+    pub wrap: bool,
+    // Ends the truncated lines with an ellipsis
+    pub ellipsis: bool,
+}
+
+impl Default for TextLayoutOptions {
+    fn default() -> Self {
+        TextLayoutOptions {
+            max_width: None,
+            max_height: None,
+            align: TextAlign::Left,
+            vertical_align: VerticalAlign::Top,
+            line_height: 1.0,
+            letter_spacing: 0.0,
+            wrap: true,
+            ellipsis: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PositionedGlyph {
+    pub character: char,
+    // Index of the glyph in the font, shaped text can draw glyphs no character maps to
+    pub glyph: u32,
+    // Pen position on the baseline, relative to the layout origin
+    pub position: glam::Vec2,
+    pub advance: f32,
+    // Markup span the glyph belongs to, 0 for plain text
+    pub span: usize,
+}
+
+// The glyphs of one line
+#[derive(Debug, Clone, Default)]
+pub struct GlyphRun {
+    pub glyphs: Vec<PositionedGlyph>,
+    pub width: f32,
+    // Relative to the layout origin
+    pub baseline: f32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TextLayout {
+    pub lines: Vec<GlyphRun>,
+    // The max width and height when they are set, otherwise the size of the lines
+    pub size: glam::Vec2,
+}
+
+struct Line {
+    glyphs: Vec<ShapedGlyph>,
+    // Justified lines don't stretch the last line of a paragraph
+    paragraph_end: bool,
+}
+
+// Vertical metrics of a font size or of an inline image, in pixels
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SpanMetrics {
+    pub ascender: f32,
+    pub line_advance: f32,
+}
+
+impl Font {
+    pub fn layout(&self, text: &str, size: u32, options: &TextLayoutOptions) -> TextLayout {
+        let metrics = match self.span_metrics(size) {
+            Some(metrics) => metrics,
+            None => return TextLayout::default(),
+        };
+        let paragraphs = text
+            .split('\n')
+            .map(|paragraph| self.shape_sized(paragraph, size))
+            .collect();
+        arrange(
+            paragraphs,
+            self.shape_sized(ELLIPSIS, size),
+            &[metrics],
+            options,
+        )
+    }
+
+    pub(crate) fn span_metrics(&self, size: u32) -> Option<SpanMetrics> {
+        let atlas = self.get_atlas(size)?;
+        let scale = self.scale(size);
+        Some(SpanMetrics {
+            ascender: atlas.ascender * scale,
+            line_advance: (atlas.line_spacing >> 6) as f32 * scale,
+        })
+    }
+
+    // Shapes a paragraph in pixels of the size, distance field glyphs are shaped at the
+    // atlas size then scaled
+    pub(crate) fn shape_sized(&self, paragraph: &str, size: u32) -> Vec<ShapedGlyph> {
+        let atlas = match self.atlas.get(&self.atlas_size(size)) {
+            Some(atlas) => atlas,
+            None => return Vec::new(),
+        };
+        let scale = self.scale(size);
+        self.shape(atlas, paragraph, self.atlas_size(size))
+            .into_iter()
+            .map(|glyph| ShapedGlyph {
+                advance: glyph.advance * scale,
+                offset: glyph.offset * scale,
+                ..glyph
+            })
+            .collect()
+    }
+}
+
+// Breaks the shaped paragraphs in lines and places them in the box of the options. The
+// glyphs index the metrics with their span, the ellipsis ends the truncated lines
+pub(crate) fn arrange(
+    paragraphs: Vec<Vec<ShapedGlyph>>,
+    ellipsis: Vec<ShapedGlyph>,
+    metrics: &[SpanMetrics],
+    options: &TextLayoutOptions,
+) -> TextLayout {
+    let advance = |glyph: &ShapedGlyph| glyph.advance + options.letter_spacing;
+    let line_width = |glyphs: &[ShapedGlyph]| {
+        let trimmed = trim_end(glyphs);
+        let width = trimmed.iter().map(advance).sum::<f32>();
+        if trimmed.is_empty() {
+            0.0
+        } else {
+            width - options.letter_spacing
+        }
+    };
+
+    // Lines are as tall as their tallest span, empty lines take the first span
+    let line_metrics = |line: &Line| {
+        line.glyphs
+            .iter()
+            .map(|glyph| metrics[glyph.span])
+            .reduce(|a, b| SpanMetrics {
+                ascender: a.ascender.max(b.ascender),
+                line_advance: a.line_advance.max(b.line_advance),
+            })
+            .unwrap_or(metrics[0])
+    };
+    let line_advance = |line: &Line| line_metrics(line).line_advance * options.line_height;
+
+    let mut lines = break_lines(paragraphs, options, &advance);
+
+    // Lines below the box are dropped, the last visible line gets the ellipsis
+    if let Some(max_height) = options.max_height {
+        let mut height = 0.0;
+        let mut max_lines = 0;
+        for line in &lines {
+            height += line_advance(line);
+            if height > max_height && max_lines > 0 {
+                break;
+            }
+            max_lines += 1;
+        }
+        if lines.len() > max_lines {
+            lines.truncate(max_lines);
+            if let (true, Some(last)) = (options.ellipsis, lines.last_mut()) {
+                truncate_line(&mut last.glyphs, options.max_width, &ellipsis, &line_width);
+            }
+        }
+    }
+    if let Some(max_width) = options.max_width {
+        let ellipsis = if options.ellipsis { &ellipsis[..] } else { &[] };
+        for line in &mut lines {
+            if line_width(&line.glyphs) > max_width {
+                truncate_line(&mut line.glyphs, Some(max_width), ellipsis, &line_width);
+            }
+        }
+    }
+
+    let widths = lines
+        .iter()
+        .map(|line| line_width(&line.glyphs))
+        .collect::<Vec<_>>();
+    let content_size = glam::vec2(
+        widths.iter().copied().fold(0.0, f32::max),
+        lines.iter().map(line_advance).sum(),
+    );
+    let box_size = glam::vec2(
+        options.max_width.unwrap_or(content_size.x),
+        options.max_height.unwrap_or(content_size.y),
+    );
+
+    let offset_y = match options.vertical_align {
+        VerticalAlign::Top => 0.0,
+        VerticalAlign::Middle => (box_size.y - content_size.y) / 2.0,
+        VerticalAlign::Bottom => box_size.y - content_size.y,
+    };
+
+    let mut runs = Vec::new();
+    let mut top = offset_y;
+    for (line, width) in lines.iter().zip(widths) {
+        // Trailing spaces are trimmed in logical order, then the line is put in visual order
+        let mut glyphs = trim_end(&line.glyphs).to_vec();
+        reorder_line(&mut glyphs);
+
+        let spaces = glyphs.iter().filter(|glyph| glyph.character == ' ').count();
+        let (offset_x, space_extra) = match options.align {
+            TextAlign::Left => (0.0, 0.0),
+            TextAlign::Center => ((box_size.x - width) / 2.0, 0.0),
+            TextAlign::Right => (box_size.x - width, 0.0),
+            TextAlign::Justify if !line.paragraph_end && spaces > 0 => {
+                (0.0, ((box_size.x - width) / spaces as f32).max(0.0))
+            }
+            TextAlign::Justify => (0.0, 0.0),
+        };
+
+        let baseline = top + line_metrics(line).ascender;
+        top += line_advance(line);
+        let mut x = offset_x;
+        let mut positioned = Vec::with_capacity(glyphs.len());
+        for glyph in &glyphs {
+            positioned.push(PositionedGlyph {
+                character: glyph.character,
+                glyph: glyph.glyph,
+                position: glam::vec2(x, baseline) + glyph.offset,
+                advance: glyph.advance,
+                span: glyph.span,
+            });
+            x += advance(glyph);
+            if glyph.character == ' ' {
+                x += space_extra;
+            }
+        }
+
+        runs.push(GlyphRun {
+            glyphs: positioned,
+            width: if space_extra > 0.0 { box_size.x } else { width },
+            baseline,
+        });
+    }
+
+    TextLayout {
+        lines: runs,
+        size: box_size,
+    }
+}
+
+fn trim_end(glyphs: &[ShapedGlyph]) -> &[ShapedGlyph] {
+    let end = glyphs
+        .iter()
+        .rposition(|glyph| !glyph.character.is_whitespace())
+        .map_or(0, |i| i + 1);
+    &glyphs[..end]
+}
+
+// Greedy line breaking, lines break after the last whitespace that fits
+fn break_lines(
+    paragraphs: Vec<Vec<ShapedGlyph>>,
+    options: &TextLayoutOptions,
+    advance: &impl Fn(&ShapedGlyph) -> f32,
+) -> Vec<Line> {
+    let max_width = options.max_width.filter(|_| options.wrap);
+    let mut lines = Vec::new();
+
+    for paragraph in paragraphs {
+        let mut glyphs: Vec<ShapedGlyph> = Vec::new();
+        let mut width = 0.0;
+        let mut break_at = None;
+
+        for glyph in paragraph {
+            width += advance(&glyph);
+            glyphs.push(glyph);
+
+            if glyph.character.is_whitespace() {
+                break_at = Some(glyphs.len());
+                continue;
+            }
+            let max_width = match max_width {
+                Some(max_width) => max_width,
+                None => continue,
+            };
+            if width - options.letter_spacing > max_width && glyphs.len() > 1 {
+                // Words longer than the line are broken before the current glyph
+                let split = match break_at {
+                    Some(split) if split < glyphs.len() => split,
+                    _ => glyphs.len() - 1,
+                };
+                let rest = glyphs.split_off(split);
+                lines.push(Line {
+                    glyphs,
+                    paragraph_end: false,
+                });
+                width = rest.iter().map(advance).sum();
+                glyphs = rest;
+                break_at = None;
+            }
+        }
+
+        lines.push(Line {
+            glyphs,
+            paragraph_end: true,
+        });
+    }
+
+    lines
+}
+
+// Removes glyphs from the end until the line fits, keeping room for the ellipsis
+fn truncate_line(
+    glyphs: &mut Vec<ShapedGlyph>,
+    max_width: Option<f32>,
+    ellipsis: &[ShapedGlyph],
+    line_width: &impl Fn(&[ShapedGlyph]) -> f32,
+) {
+    let fits = |glyphs: &[ShapedGlyph]| {
+        let mut line = trim_end(glyphs).to_vec();
+        line.extend_from_slice(ellipsis);
+        max_width.map_or(true, |max_width| line_width(&line) <= max_width)
+    };
+
+    while !glyphs.is_empty() && !fits(glyphs) {
+        glyphs.pop();
+    }
+    let end = trim_end(glyphs).len();
+    glyphs.truncate(end);
+
+    // The ellipsis follows the direction of the text it ends
+    let level = glyphs.last().map_or(0, |glyph| glyph.level);
+    glyphs.extend(ellipsis.iter().map(|glyph| ShapedGlyph { level, ..*glyph }));
+}
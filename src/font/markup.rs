@@ -0,0 +1,363 @@
+use std::rc::Rc;
+
+use super::{
+    layout::{arrange, SpanMetrics, TextLayout, TextLayoutOptions, ELLIPSIS},
+    shaping::ShapedGlyph,
+    Font,
+};
+use crate::render::texture::Texture;
+
+// Stands for an inline image in the laid out glyphs
+pub(crate) const IMAGE_CHARACTER: char = '\u{FFFC}';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlyphAnimation {
+    // Glyphs move up and down one after the other
+    Wave,
+    // Glyphs jitter around their position
+    Shake,
+}
+
+impl GlyphAnimation {
+    // Offset of the glyph at the index of the text, time in seconds
+    pub(crate) fn offset(self, time: f32, index: usize, size: u32) -> glam::Vec2 {
+        let amplitude = size as f32 * 0.1;
+        match self {
+            GlyphAnimation::Wave => {
+                glam::vec2(0.0, (time * 6.0 + index as f32 * 0.6).sin() * amplitude)
+            }
+            GlyphAnimation::Shake => {
+                // A new offset 20 times per second, different for each glyph
+                let seed = ((time * 20.0) as u32).wrapping_mul(0x9E37_79B9) ^ index as u32;
+                glam::vec2(hash(seed), hash(seed ^ 0x5555_5555)) * amplitude * 0.5
+            }
+        }
+    }
+}
+
+// Style of a span, unset values come from the label
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextStyle {
+    pub color: Option<glam::Vec4>,
+    pub size: Option<u32>,
+    // Name of a font registered on the label
+    pub font: Option<String>,
+    pub bold: bool,
+    pub animation: Option<GlyphAnimation>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpanContent {
+    Text(String),
+    // Name of an image registered on the label
+    Image(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextSpan {
+    pub content: SpanContent,
+    pub style: TextStyle,
+}
+
+// Splits the text in styled spans. Supported tags are [color=#rgb], [color=#rrggbb],
+// [color=#rrggbbaa], [b], [size=32], [font=name], [wave], [shake] and their closing tags,
+// and [img=name]. "[[" is a literal "[", unknown tags are kept as text
+pub fn parse_markup(text: &str) -> Vec<TextSpan> {
+    let mut spans = Vec::new();
+    let mut style = TextStyle::default();
+    // Open tags with the style they replaced
+    let mut stack: Vec<(&str, TextStyle)> = Vec::new();
+    let mut current = String::new();
+
+    let mut rest = text;
+    while let Some(start) = rest.find('[') {
+        current.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if rest.starts_with("[[") {
+            current.push('[');
+            rest = &rest[2..];
+            continue;
+        }
+        let end = match rest.find(']') {
+            Some(end) => end,
+            None => break,
+        };
+        let tag = &rest[1..end];
+
+        if let Some(name) = tag.strip_prefix('/') {
+            match stack.iter().rposition(|(open, _)| *open == name) {
+                Some(index) => {
+                    flush(&mut spans, &mut current, &style);
+                    style = stack[index].1.clone();
+                    stack.truncate(index);
+                }
+                None => current.push_str(&rest[..=end]),
+            }
+        } else if let Some(image) = tag.strip_prefix("img=") {
+            flush(&mut spans, &mut current, &style);
+            spans.push(TextSpan {
+                content: SpanContent::Image(image.to_string()),
+                style: style.clone(),
+            });
+        } else if let Some(new_style) = apply_tag(tag, &style) {
+            flush(&mut spans, &mut current, &style);
+            let name = tag.split('=').next().unwrap_or(tag);
+            stack.push((name, std::mem::replace(&mut style, new_style)));
+        } else {
+            current.push_str(&rest[..=end]);
+        }
+        rest = &rest[end + 1..];
+    }
+    current.push_str(rest);
+    flush(&mut spans, &mut current, &style);
+    spans
+}
+
+fn flush(spans: &mut Vec<TextSpan>, current: &mut String, style: &TextStyle) {
+    if !current.is_empty() {
+        spans.push(TextSpan {
+            content: SpanContent::Text(std::mem::take(current)),
+            style: style.clone(),
+        });
+    }
+}
+
+// The style inside an opening tag, None when the tag is unknown
+fn apply_tag(tag: &str, style: &TextStyle) -> Option<TextStyle> {
+    let (name, value) = match tag.split_once('=') {
+        Some((name, value)) => (name, Some(value)),
+        None => (tag, None),
+    };
+    let mut style = style.clone();
+    match (name, value) {
+        ("b", None) => style.bold = true,
+        ("wave", None) => style.animation = Some(GlyphAnimation::Wave),
+        ("shake", None) => style.animation = Some(GlyphAnimation::Shake),
+        ("color", Some(value)) => style.color = Some(parse_color(value)?),
+        ("size", Some(value)) => style.size = Some(value.parse().ok()?),
+        ("font", Some(value)) => style.font = Some(value.to_string()),
+        _ => return None,
+    }
+    Some(style)
+}
+
+fn parse_color(value: &str) -> Option<glam::Vec4> {
+    let hex = value.strip_prefix('#')?;
+    let digits = hex
+        .chars()
+        .map(|c| c.to_digit(16).map(|digit| digit as f32))
+        .collect::<Option<Vec<_>>>()?;
+    let channels = match digits.len() {
+        // Short forms repeat each digit
+        3 | 4 => digits.iter().map(|digit| digit * 17.0).collect::<Vec<_>>(),
+        6 | 8 => digits
+            .chunks(2)
+            .map(|pair| pair[0] * 16.0 + pair[1])
+            .collect(),
+        _ => return None,
+    };
+    let alpha = channels.get(3).copied().unwrap_or(255.0);
+    Some(glam::vec4(channels[0], channels[1], channels[2], alpha) / 255.0)
+}
+
+// Maps the seed to -1..1
+fn hash(seed: u32) -> f32 {
+    let mut x = seed;
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7FEB_352D);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846C_A68B);
+    x ^= x >> 16;
+    (x & 0xFFFF) as f32 / 65535.0 * 2.0 - 1.0
+}
+
+pub struct RichImage {
+    pub texture: Rc<Texture>,
+    pub texture_coords: [glam::Vec2; 4],
+    // Width over height, the image is as tall as the size of its span
+    pub aspect: f32,
+}
+
+pub enum RichContent {
+    Text(String),
+    Image(RichImage),
+}
+
+// A span with its font and images resolved
+pub struct RichSpan {
+    pub content: RichContent,
+    pub font: Rc<Font>,
+    // Font the span falls back from, the span keeps its baseline and line height
+    pub primary: Option<Rc<Font>>,
+    pub size: u32,
+    pub color: glam::Vec4,
+    // Drawn twice with a small offset, used when there's no bold font
+    pub bold: bool,
+    pub animation: Option<GlyphAnimation>,
+}
+
+// Lays out the spans as a single text, the glyphs keep the index of their span
+pub fn layout_rich(spans: &[RichSpan], options: &TextLayoutOptions) -> TextLayout {
+    let mut paragraphs: Vec<Vec<ShapedGlyph>> = vec![Vec::new()];
+    let mut metrics = Vec::with_capacity(spans.len());
+
+    for (index, span) in spans.iter().enumerate() {
+        let size = span.size as f32;
+        let metrics_font = span.primary.as_ref().unwrap_or(&span.font);
+        let font_metrics = metrics_font.span_metrics(span.size).unwrap_or(SpanMetrics {
+            ascender: size,
+            line_advance: size,
+        });
+        match &span.content {
+            RichContent::Text(text) => {
+                metrics.push(font_metrics);
+                for (line, paragraph) in text.split('\n').enumerate() {
+                    if line > 0 {
+                        paragraphs.push(Vec::new());
+                    }
+                    let glyphs = span.font.shape_sized(paragraph, span.size);
+                    paragraphs
+                        .last_mut()
+                        .unwrap()
+                        .extend(glyphs.into_iter().map(|glyph| ShapedGlyph {
+                            span: index,
+                            ..glyph
+                        }));
+                }
+            }
+            RichContent::Image(image) => {
+                // Sits on the baseline
+                metrics.push(SpanMetrics {
+                    ascender: size,
+                    line_advance: font_metrics.line_advance.max(size),
+                });
+                paragraphs.last_mut().unwrap().push(ShapedGlyph {
+                    glyph: 0,
+                    character: IMAGE_CHARACTER,
+                    advance: size * image.aspect,
+                    offset: glam::Vec2::ZERO,
+                    level: 0,
+                    span: index,
+                });
+            }
+        }
+    }
+
+    // The ellipsis is drawn with the font of the first text span
+    let ellipsis = spans
+        .iter()
+        .enumerate()
+        .find(|(_, span)| matches!(span.content, RichContent::Text(_)))
+        .map(|(index, span)| {
+            span.font
+                .shape_sized(ELLIPSIS, span.size)
+                .into_iter()
+                .map(|glyph| ShapedGlyph {
+                    span: index,
+                    ..glyph
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if metrics.is_empty() {
+        return TextLayout::default();
+    }
+    arrange(paragraphs, ellipsis, &metrics, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(content: &str, style: TextStyle) -> TextSpan {
+        TextSpan {
+            content: SpanContent::Text(content.to_string()),
+            style,
+        }
+    }
+
+    #[test]
+    fn nested_tags_restore_the_outer_style() {
+        let bold = TextStyle {
+            bold: true,
+            ..TextStyle::default()
+        };
+        let bold_big = TextStyle {
+            size: Some(32),
+            ..bold.clone()
+        };
+        assert_eq!(
+            parse_markup("a[b]b[size=32]c[/size]d[/b]e"),
+            vec![
+                text("a", TextStyle::default()),
+                text("b", bold.clone()),
+                text("c", bold_big),
+                text("d", bold),
+                text("e", TextStyle::default()),
+            ]
+        );
+    }
+
+    #[test]
+    fn closing_an_outer_tag_closes_the_inner_ones() {
+        let wave = TextStyle {
+            animation: Some(GlyphAnimation::Wave),
+            ..TextStyle::default()
+        };
+        let wave_red = TextStyle {
+            color: Some(glam::vec4(1.0, 0.0, 0.0, 1.0)),
+            ..wave.clone()
+        };
+        assert_eq!(
+            parse_markup("[wave]a[color=#f00]b[/wave]c[/color]"),
+            vec![
+                text("a", wave),
+                text("b", wave_red),
+                text("c[/color]", TextStyle::default()),
+            ]
+        );
+    }
+
+    #[test]
+    fn unclosed_tags_style_the_rest_of_the_text() {
+        let bold = TextStyle {
+            bold: true,
+            ..TextStyle::default()
+        };
+        assert_eq!(
+            parse_markup("a[b]bc"),
+            vec![text("a", TextStyle::default()), text("bc", bold)]
+        );
+    }
+
+    #[test]
+    fn unterminated_brackets_and_unknown_tags_are_text() {
+        assert_eq!(
+            parse_markup("a[[b] [nope]c [b"),
+            vec![text("a[b] [nope]c [b", TextStyle::default())]
+        );
+        assert_eq!(
+            parse_markup("[/b]a"),
+            vec![text("[/b]a", TextStyle::default())]
+        );
+    }
+
+    #[test]
+    fn images_keep_the_current_style() {
+        let bold = TextStyle {
+            bold: true,
+            ..TextStyle::default()
+        };
+        assert_eq!(
+            parse_markup("[b]a[img=coin][/b]"),
+            vec![
+                text("a", bold.clone()),
+                TextSpan {
+                    content: SpanContent::Image("coin".to_string()),
+                    style: bold,
+                },
+            ]
+        );
+    }
+}
@@ -1,7 +1,12 @@
-use image::{DynamicImage, Rgba};
+use image::{DynamicImage, GenericImage, GrayImage, Luma};
 use log::warn;
 use std::{collections::HashMap, path::Path, rc::Rc};
 
+use self::{
+    bmfont::BitmapFont,
+    layout::{PositionedGlyph, TextLayout, TextLayoutOptions},
+    sdf::{SDF_SIZE, SDF_SPREAD},
+};
 use crate::{
     error::{BreakoutError, BreakoutResult},
     render::{subtexture::SubTexture, texture::Texture},
@@ -9,302 +14,714 @@ use crate::{
 };
 extern crate freetype;
 
-struct GlyphImage {
-    size: glam::IVec2,
-    bearing: glam::IVec2,
-    advance: u32,
-    character: char,
-    buffer: Vec<u8>,
-}
+mod bmfont;
+pub mod layout;
+pub mod markup;
+pub mod sdf;
+mod shaping;
+
+// Glyph pages start small and grow in height before a new page is added
+const GLYPH_PAGE_WIDTH: u32 = 512;
+const GLYPH_PAGE_MIN_HEIGHT: u32 = 64;
+const GLYPH_PAGE_MAX_HEIGHT: u32 = 2048;
+const GLYPH_PADDING: u32 = 2;
+// Drawn instead of the characters the font doesn't have, the first one the font has is used
+const REPLACEMENT_CHARACTERS: [char; 2] = ['\u{FFFD}', '?'];
 
 pub struct Character {
-    sub_texture: SubTexture,
+    page: usize,
+    region: Rect,
     size: glam::IVec2,
     bearing: glam::IVec2,
     advance: u32,
 }
 
+struct GlyphPage {
+    // Empty for the pages of bitmap fonts, their glyphs are packed in the texture
+    image: GrayImage,
+    texture: Option<Rc<Texture>>,
+    // Shelf packing cursor
+    x: u32,
+    y: u32,
+    shelf_height: u32,
+    // The texture is created again when new glyphs were added
+    dirty: bool,
+}
+
+impl GlyphPage {
+    fn new(height: u32) -> GlyphPage {
+        GlyphPage {
+            image: GrayImage::new(GLYPH_PAGE_WIDTH, height),
+            texture: None,
+            x: 0,
+            y: 0,
+            shelf_height: 0,
+            dirty: true,
+        }
+    }
+
+    fn from_texture(texture: Texture) -> GlyphPage {
+        GlyphPage {
+            image: GrayImage::new(0, 0),
+            texture: Some(Rc::new(texture)),
+            x: 0,
+            y: 0,
+            shelf_height: 0,
+            dirty: false,
+        }
+    }
+
+    // Returns the top left corner of the reserved space, the page grows when needed
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let (width, height) = (width + GLYPH_PADDING, height + GLYPH_PADDING);
+        if width > GLYPH_PAGE_WIDTH {
+            return None;
+        }
+        if self.x + width > GLYPH_PAGE_WIDTH {
+            self.x = 0;
+            self.y += self.shelf_height;
+            self.shelf_height = 0;
+        }
+        if self.y + height > GLYPH_PAGE_MAX_HEIGHT {
+            return None;
+        }
+        if self.y + height > self.image.height() {
+            let mut new_height = self.image.height();
+            while self.y + height > new_height {
+                new_height *= 2;
+            }
+            let mut image = GrayImage::new(GLYPH_PAGE_WIDTH, new_height.min(GLYPH_PAGE_MAX_HEIGHT));
+            image
+                .copy_from(&self.image, 0, 0)
+                .expect("The glyph page only grows");
+            self.image = image;
+        }
+
+        let position = (self.x, self.y);
+        self.x += width;
+        self.shelf_height = self.shelf_height.max(height);
+        Some(position)
+    }
+}
+
 pub struct FontAtlas {
-    texture: Rc<Texture>,
-    characters: HashMap<char, Character>,
+    pages: Vec<GlyphPage>,
+    // Keyed by glyph index, shaped text can use glyphs that no character maps to
+    glyphs: HashMap<u32, Character>,
     line_spacing: u32,
+    // Distance from the top of the line to the baseline in pixels
+    ascender: f32,
+    replacement: u32,
+}
+
+// Drawn as nothing when even the replacement glyph is missing
+static EMPTY_CHARACTER: Character = Character {
+    page: 0,
+    region: Rect {
+        x: 0.0,
+        y: 0.0,
+        width: 0.0,
+        height: 0.0,
+    },
+    size: glam::IVec2::ZERO,
+    bearing: glam::IVec2::ZERO,
+    advance: 0,
+};
+
+impl FontAtlas {
+    // Glyphs that are not cached yet or missing from the font get the replacement glyph
+    fn glyph(&self, index: u32) -> &Character {
+        self.glyphs
+            .get(&index)
+            .or_else(|| self.glyphs.get(&self.replacement))
+            .unwrap_or(&EMPTY_CHARACTER)
+    }
+
+    fn texture(&self, character: &Character) -> &Rc<Texture> {
+        self.pages[character.page]
+            .texture
+            .as_ref()
+            .expect("Glyph pages are uploaded when glyphs are cached")
+    }
+
+    fn texture_coords(&self, character: &Character) -> [glam::Vec2; 4] {
+        SubTexture::from_texture(character.region, self.texture(character))
+            .texture_coords
+            .unwrap_or(crate::render::vertex::TEXTURE_COORDS)
+    }
+}
+
+enum FontSource {
+    // Glyphs are rasterized when they are first drawn
+    FreeType {
+        face: freetype::Face,
+        // The shaper reads the font tables from the same bytes
+        #[cfg(feature = "shaping")]
+        data: Rc<Vec<u8>>,
+    },
+    // Glyphs drawn by hand, loaded with their pages
+    Bitmap(BitmapFont),
+}
+
+// A laid out glyph ready to draw. The texture is looked up by page when drawn, since the
+// page texture is created again when glyphs are added
+#[derive(Debug, Clone, Copy)]
+pub struct GlyphQuad {
+    pub page: usize,
+    pub vertices: [glam::Vec3; 4],
+    // Pixels of the glyph in the page, pages grow so it's normalized when drawn
+    pub region: Rect,
+}
+
+impl GlyphQuad {
+    pub(crate) fn texture_coords(&self, texture: &Texture) -> [glam::Vec2; 4] {
+        SubTexture::from_texture(self.region, texture)
+            .texture_coords
+            .unwrap_or(crate::render::vertex::TEXTURE_COORDS)
+    }
 }
 
 pub struct Font {
-    face: freetype::Face,
+    source: FontSource,
     atlas: HashMap<u32, FontAtlas>,
+    // Every size is drawn from a single distance field atlas
+    sdf: bool,
 }
 
 impl Font {
     pub fn new(path: &str) -> BreakoutResult<Font> {
-        let lib = freetype::Library::init().map_err(BreakoutError::FontError)?;
         let font_name = Path::new(path);
         if !font_name.exists() {
             panic!("ERROR::FREETYPE: Failed to load font_name");
         }
-        let face = lib
-            .new_face(font_name, 0)
-            .map_err(BreakoutError::FontError)?;
-
-        let atlas = HashMap::new();
-        Ok(Font { face, atlas })
+        let buffer = std::fs::read(font_name).map_err(BreakoutError::IOError)?;
+        Font::from_data(Rc::new(buffer), false)
     }
 
     pub fn new_from_memory(buffer: &[u8]) -> BreakoutResult<Font> {
+        Font::from_data(Rc::new(buffer.to_vec()), false)
+    }
+
+    // Stays crisp at any size, scale and rotation, and can be drawn with an outline or a glow
+    pub fn new_sdf(path: &str) -> BreakoutResult<Font> {
+        let buffer = std::fs::read(path).map_err(BreakoutError::IOError)?;
+        Font::from_data(Rc::new(buffer), true)
+    }
+
+    pub fn new_sdf_from_memory(buffer: &[u8]) -> BreakoutResult<Font> {
+        Font::from_data(Rc::new(buffer.to_vec()), true)
+    }
+
+    fn from_data(data: Rc<Vec<u8>>, sdf: bool) -> BreakoutResult<Font> {
         let lib = freetype::Library::init().map_err(BreakoutError::FontError)?;
         let face = lib
-            .new_memory_face(buffer.to_vec(), 0)
+            .new_memory_face(data.clone(), 0)
             .map_err(BreakoutError::FontError)?;
 
         let atlas = HashMap::new();
-        Ok(Font { face, atlas })
-    }
-
-    pub fn has_size(&self, size: u32) -> bool {
-        self.atlas.contains_key(&size)
+        Ok(Font {
+            source: FontSource::FreeType {
+                face,
+                #[cfg(feature = "shaping")]
+                data,
+            },
+            atlas,
+            sdf,
+        })
     }
 
-    pub fn build_with_size<F>(&mut self, size: u32, get_texture: F) -> BreakoutResult
+    // Loads an AngelCode BMFont descriptor (text, XML or binary) and its pages, the font
+    // is scaled from the size it was drawn at
+    pub fn new_bitmap<F>(path: &str, mut get_texture: F) -> BreakoutResult<Font>
     where
-        F: FnOnce(DynamicImage) -> Texture,
+        F: FnMut(DynamicImage) -> BreakoutResult<Texture>,
     {
-        if self.has_size(size) {
-            return Ok(());
+        let bytes = std::fs::read(path).map_err(BreakoutError::IOError)?;
+        let font = BitmapFont::parse(&bytes)?;
+        let directory = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+
+        let mut pages = Vec::with_capacity(font.pages.len());
+        for file in &font.pages {
+            let image = image::open(directory.join(file)).map_err(BreakoutError::ImageError)?;
+            pages.push(GlyphPage::from_texture(get_texture(image)?));
         }
-        // set size to load glyphs as
-        self.face
-            .set_pixel_sizes(0, size)
-            .map_err(BreakoutError::FontError)?;
 
-        let mut glyph_images = Vec::new();
-        let mut max_width = 0;
-        let mut max_height = 0;
+        let glyphs = font
+            .chars
+            .iter()
+            .map(|(id, c)| {
+                let character = Character {
+                    page: c.page,
+                    region: Rect::new(c.x as f32, c.y as f32, c.width as f32, c.height as f32),
+                    size: glam::ivec2(c.width as i32, c.height as i32),
+                    bearing: glam::ivec2(c.x_offset, font.base as i32 - c.y_offset),
+                    advance: (c.x_advance.max(0) as u32) << 6,
+                };
+                (*id, character)
+            })
+            .collect::<HashMap<_, _>>();
+        // Any glyph is better than nothing
+        let replacement = REPLACEMENT_CHARACTERS
+            .iter()
+            .chain(&[' '])
+            .map(|c| *c as u32)
+            .find(|id| glyphs.contains_key(id))
+            .unwrap_or_else(|| *glyphs.keys().next().expect("Bitmap fonts have glyphs"));
+
+        let mut atlas = HashMap::new();
+        atlas.insert(
+            font.size,
+            FontAtlas {
+                pages,
+                glyphs,
+                line_spacing: font.line_height << 6,
+                ascender: font.base as f32,
+                replacement,
+            },
+        );
+        Ok(Font {
+            source: FontSource::Bitmap(font),
+            atlas,
+            sdf: false,
+        })
+    }
 
-        let mut chars = Vec::new();
-        chars.extend(32..128usize);
-        chars.extend(160..255usize);
+    pub fn has_size(&self, size: u32) -> bool {
+        self.atlas.contains_key(&self.atlas_size(size))
+    }
 
-        for c in chars {
-            self.face
-                .load_char(c, freetype::face::LoadFlag::RENDER)
-                .map_err(BreakoutError::FontError)?;
+    pub fn is_sdf(&self) -> bool {
+        self.sdf
+    }
 
-            let bitmap = self.face.glyph().bitmap();
-            let bytes = bitmap.buffer();
+    pub fn has_char(&self, c: char) -> bool {
+        self.char_index(c) != 0
+    }
 
-            let (width, height) = (bitmap.width(), bitmap.rows());
+    // Size the glyphs of the text size are rasterized at
+    fn atlas_size(&self, size: u32) -> u32 {
+        match &self.source {
+            FontSource::Bitmap(font) => font.size,
+            FontSource::FreeType { .. } if self.sdf => SDF_SIZE,
+            FontSource::FreeType { .. } => size,
+        }
+    }
 
-            glyph_images.push(GlyphImage {
-                character: c as u8 as char,
-                buffer: bytes.to_vec(),
-                size: glam::ivec2(width, height),
-                bearing: glam::ivec2(
-                    self.face.glyph().bitmap_left(),
-                    self.face.glyph().bitmap_top(),
-                ),
-                advance: self.face.glyph().advance().x as u32,
-            });
+    // From the atlas pixels to the pixels of the text size
+    fn scale(&self, size: u32) -> f32 {
+        size as f32 / self.atlas_size(size) as f32
+    }
 
-            max_width = width.max(max_width);
-            max_height = height.max(max_height);
+    fn get_atlas(&self, size: u32) -> Option<&FontAtlas> {
+        let atlas = self.atlas.get(&self.atlas_size(size));
+        if atlas.is_none() {
+            warn!("Font should be build before");
         }
+        atlas
+    }
 
-        let spacing = 4;
-        let columns = 10; // 512 / (max_width + spacing);
-        let rows = (glyph_images.len() as i32 / columns) + 1;
-        let image_width = columns * max_width + spacing * columns;
-        let image_height = rows * max_height + spacing * rows;
+    // Converts pixels of the text size to a distance of the field, 0.5 being the outline
+    pub(crate) fn sdf_distance(&self, size: u32, pixels: f32) -> f32 {
+        pixels / self.scale(size) / (2.0 * SDF_SPREAD as f32)
+    }
 
-        let mut image = DynamicImage::new_rgba8(image_width as u32, image_height as u32).to_rgba8();
-        let mut characters = HashMap::new();
+    // True when every glyph of the text the font has is cached
+    pub fn has_text(&self, text: &str, size: u32) -> bool {
+        match (&self.source, self.atlas.get(&self.atlas_size(size))) {
+            // Every glyph of a bitmap font is loaded with it
+            (FontSource::Bitmap(_), _) => true,
+            (_, Some(atlas)) => self
+                .glyph_indices(text)
+                .iter()
+                .all(|glyph| *glyph == 0 || atlas.glyphs.contains_key(glyph)),
+            (_, None) => false,
+        }
+    }
 
-        for (index, glyph_image) in glyph_images.iter().enumerate() {
-            let x = index as i32 % columns;
-            let y = index as i32 / columns;
+    // Caches the printable ASCII characters, others are added by cache_text
+    pub fn build_with_size<F>(&mut self, size: u32, get_texture: F) -> BreakoutResult
+    where
+        F: FnMut(DynamicImage) -> BreakoutResult<Texture>,
+    {
+        if self.has_size(size) {
+            return Ok(());
+        }
+        let glyphs = self.glyph_indices(&ascii());
+        self.cache_glyphs(size, glyphs, get_texture)
+    }
 
-            let x_offset = x * (max_width + spacing);
-            let y_offset = y * (max_height + spacing);
+    // Rasterizes the glyphs of the text that are not cached yet
+    pub fn cache_text<F>(&mut self, text: &str, size: u32, get_texture: F) -> BreakoutResult
+    where
+        F: FnMut(DynamicImage) -> BreakoutResult<Texture>,
+    {
+        let mut glyphs = self.glyph_indices(text);
+        if let Some(atlas) = self.atlas.get(&self.atlas_size(size)) {
+            glyphs.retain(|glyph| !atlas.glyphs.contains_key(glyph));
+            if glyphs.is_empty() {
+                return Ok(());
+            }
+        } else {
+            glyphs.extend(self.glyph_indices(&ascii()));
+        }
+        self.cache_glyphs(size, glyphs, get_texture)
+    }
 
-            let rect = Rect::from_position_size(
-                glam::ivec2(x_offset, y_offset).as_vec2().into(),
-                glyph_image.size.as_vec2().into(),
-            );
-            let sub_texture =
-                SubTexture::new_with_texture_size(rect, image_width as f32, image_height as f32);
+    // 0 when the font doesn't have the character, bitmap fonts use the character as index
+    fn char_index(&self, c: char) -> u32 {
+        match &self.source {
+            FontSource::FreeType { face, .. } => face.get_char_index(c as usize),
+            FontSource::Bitmap(font) if font.chars.contains_key(&(c as u32)) => c as u32,
+            FontSource::Bitmap(_) => 0,
+        }
+    }
 
-            characters.insert(
-                glyph_image.character,
-                Character {
-                    sub_texture,
-                    size: glyph_image.size,
-                    advance: glyph_image.advance,
-                    bearing: glyph_image.bearing,
-                },
-            );
-            let width = glyph_image.size.x;
-            for (i, b) in glyph_image.buffer.iter().enumerate() {
-                let x = i as u32 % width as u32;
-                let y = i as u32 / width as u32;
-                let px = image.get_pixel_mut(x_offset as u32 + x, y_offset as u32 + y);
-                *px = Rgba([255, 255, 255, *b as u8]);
+    // Kerning between two glyphs in pixels of the size
+    fn kerning(&self, left: u32, right: u32, size: u32) -> f32 {
+        match &self.source {
+            FontSource::FreeType { face, .. } => {
+                if !face.has_kerning() {
+                    return 0.0;
+                }
+                // The kerning is read unscaled, the face size is shared by every atlas
+                let scale = size as f32 / face.em_size().max(1) as f32;
+                face.get_kerning(left, right, freetype::face::KerningMode::KerningUnscaled)
+                    .map_or(0.0, |delta| delta.x as f32 * scale)
             }
+            FontSource::Bitmap(font) => font.kernings.get(&(left, right)).map_or(0.0, |amount| {
+                *amount as f32 * size as f32 / font.size as f32
+            }),
         }
+    }
 
-        // image.save("debug_font_atlas.png").unwrap();
-        let texture = Rc::new(get_texture(DynamicImage::ImageRgba8(image)));
-
-        let line_spacing = if let Some(metrics) = self.face.size_metrics() {
-            metrics.height as u32
-        } else {
-            warn!("It whould have the size_metrics");
-            self.face.height() as u32
+    fn cache_glyphs<F>(&mut self, size: u32, glyphs: Vec<u32>, mut get_texture: F) -> BreakoutResult
+    where
+        F: FnMut(DynamicImage) -> BreakoutResult<Texture>,
+    {
+        // The pages of bitmap fonts are loaded with the font
+        let face = match &self.source {
+            FontSource::FreeType { face, .. } => face,
+            FontSource::Bitmap(_) => return Ok(()),
         };
+        let size = self.atlas_size(size);
+        // set size to load glyphs as, the face is shared by every size
+        face.set_pixel_sizes(0, size)
+            .map_err(BreakoutError::FontError)?;
 
-        let font_atlas = FontAtlas {
-            texture,
-            characters,
-            line_spacing,
-        };
+        if !self.atlas.contains_key(&size) {
+            let line_spacing = if let Some(metrics) = face.size_metrics() {
+                metrics.height as u32
+            } else {
+                warn!("It whould have the size_metrics");
+                face.height() as u32
+            };
+            let ascender = match face.size_metrics() {
+                Some(metrics) => (metrics.ascender >> 6) as f32,
+                None => size as f32,
+            };
+            // The missing glyph of the font (index 0) is the last resort
+            let replacement = REPLACEMENT_CHARACTERS
+                .iter()
+                .map(|c| self.char_index(*c))
+                .find(|index| *index != 0)
+                .unwrap_or(0);
+
+            self.atlas.insert(
+                size,
+                FontAtlas {
+                    pages: Vec::new(),
+                    glyphs: HashMap::new(),
+                    line_spacing,
+                    ascender,
+                    replacement,
+                },
+            );
+        }
 
-        self.atlas.insert(size, font_atlas);
+        let atlas = self.atlas.get_mut(&size).unwrap();
+        let replacement = std::iter::once(atlas.replacement);
+        // Glyph 0 is only drawn as the replacement glyph
+        let glyphs = glyphs.into_iter().filter(|glyph| *glyph != 0);
+        Font::rasterize(face, atlas, self.sdf, replacement.chain(glyphs))?;
+
+        for page in atlas.pages.iter_mut().filter(|page| page.dirty) {
+            // image.save("debug_font_atlas.png").unwrap();
+            let image = DynamicImage::ImageLuma8(page.image.clone());
+            page.texture = Some(Rc::new(get_texture(image)?));
+            page.dirty = false;
+        }
 
         Ok(())
     }
 
-    pub fn measure(&self, text: &str, size: u32) -> glam::Vec2 {
-        if !self.atlas.contains_key(&size) {
-            warn!("Font should be build before");
-            return glam::Vec2::ZERO;
-        }
-
-        let atlas = &self.atlas[&size];
-        let scale = 1.0;
-
-        let mut max_x_pos = 0.0;
-        let h_bearing_y = &atlas.characters[&'H'].bearing.y;
+    fn rasterize(
+        face: &freetype::Face,
+        atlas: &mut FontAtlas,
+        sdf: bool,
+        glyphs: impl Iterator<Item = u32>,
+    ) -> BreakoutResult {
+        for index in glyphs {
+            if atlas.glyphs.contains_key(&index) {
+                continue;
+            }
 
-        let mut x_pos = 0.0;
-        let mut y_pos = *h_bearing_y as f32;
+            face.load_glyph(index, freetype::face::LoadFlag::RENDER)
+                .map_err(BreakoutError::FontError)?;
 
-        for c in text.chars() {
-            if c.is_control() {
-                if c == '\n' {
-                    x_pos = 0.0;
-                    y_pos += (atlas.line_spacing >> 6) as f32;
+            let glyph = face.glyph();
+            let bitmap = glyph.bitmap();
+            // The pitch can be bigger than the width
+            let pitch = bitmap.pitch().unsigned_abs() as usize;
+            let buffer = bitmap.buffer();
+            let coverage =
+                GrayImage::from_fn(bitmap.width() as u32, bitmap.rows() as u32, |x, y| {
+                    Luma([buffer[y as usize * pitch + x as usize]])
+                });
+            let mut bearing = glam::ivec2(glyph.bitmap_left(), glyph.bitmap_top());
+
+            // Empty glyphs like spaces stay empty so they are not drawn
+            let image = if sdf && coverage.width() > 0 && coverage.height() > 0 {
+                bearing += glam::ivec2(-(SDF_SPREAD as i32), SDF_SPREAD as i32);
+                sdf::signed_distance_field(&coverage, SDF_SPREAD)
+            } else {
+                coverage
+            };
+            let (width, height) = image.dimensions();
+
+            let mut allocation = atlas
+                .pages
+                .last_mut()
+                .and_then(|page| page.allocate(width, height));
+            if allocation.is_none() {
+                let mut page = GlyphPage::new(GLYPH_PAGE_MIN_HEIGHT);
+                allocation = page.allocate(width, height);
+                atlas.pages.push(page);
+            }
+            let (x_offset, y_offset) = match allocation {
+                Some(allocation) => allocation,
+                // Kept as an empty glyph so it's not rasterized again
+                None => {
+                    warn!("Glyph {} is too big for the glyph page", index);
+                    atlas.glyphs.insert(
+                        index,
+                        Character {
+                            advance: glyph.advance().x as u32,
+                            ..EMPTY_CHARACTER
+                        },
+                    );
                     continue;
                 }
-            }
-
-            if c.is_whitespace() {}
+            };
 
-            let character = &atlas.characters[&c];
+            let page_index = atlas.pages.len() - 1;
+            let page = &mut atlas.pages[page_index];
+            page.image
+                .copy_from(&image, x_offset, y_offset)
+                .expect("The glyph was allocated in the page");
+            page.dirty = true;
 
-            x_pos += (character.advance >> 6) as f32 * scale;
-            if x_pos > max_x_pos {
-                max_x_pos = x_pos;
-            }
+            atlas.glyphs.insert(
+                index,
+                Character {
+                    page: page_index,
+                    region: Rect::new(
+                        x_offset as f32,
+                        y_offset as f32,
+                        width as f32,
+                        height as f32,
+                    ),
+                    size: glam::ivec2(width as i32, height as i32),
+                    bearing,
+                    advance: glyph.advance().x as u32,
+                },
+            );
         }
 
-        glam::vec2(max_x_pos, y_pos)
+        Ok(())
+    }
+
+    pub fn measure(&self, text: &str, size: u32) -> glam::Vec2 {
+        self.layout(text, size, &TextLayoutOptions::default()).size
+    }
+
+    // Sum of the glyph advances, unlike measure the trailing whitespace is counted
+    pub fn advance(&self, text: &str, size: u32) -> f32 {
+        self.shape_sized(text, size)
+            .iter()
+            .map(|glyph| glyph.advance)
+            .sum()
     }
 
     pub fn draw<F>(&self, text: &str, size: u32, mut render: F)
     where
         F: FnMut(&Texture, glam::Vec2, Rect),
     {
-        if !self.atlas.contains_key(&size) {
-            warn!("Font should be build before");
-            return;
+        let atlas = match self.get_atlas(size) {
+            Some(atlas) => atlas,
+            None => return,
+        };
+        let scale = self.scale(size);
+
+        let layout = self.layout(text, size, &TextLayoutOptions::default());
+        for glyph in layout.lines.iter().flat_map(|line| &line.glyphs) {
+            let character = atlas.glyph(glyph.glyph);
+            let position = glyph.position
+                + glam::vec2(character.bearing.x as f32, -character.bearing.y as f32) * scale;
+            render(atlas.texture(character), position, character.region);
         }
+    }
 
-        let atlas = &self.atlas[&size];
-        let scale = 1.0;
-        let mut x_pos = 0.0;
-        let mut y_pos = 0.0;
+    pub fn draw_vertices<F>(&self, text: &str, position: glam::Vec2, size: u32, render: F)
+    where
+        F: FnMut(&Rc<Texture>, [glam::Vec3; 4], [glam::Vec2; 4]),
+    {
+        let layout = self.layout(text, size, &TextLayoutOptions::default());
+        self.draw_layout(
+            &layout,
+            glam::Affine2::from_translation(position),
+            size,
+            render,
+        );
+    }
 
-        let h_bearing_y = &atlas.characters[&'H'].bearing.y;
+    // The glyphs are laid out from the origin and moved by the transform
+    pub fn draw_layout<F>(
+        &self,
+        layout: &TextLayout,
+        transform: glam::Affine2,
+        size: u32,
+        mut render: F,
+    ) where
+        F: FnMut(&Rc<Texture>, [glam::Vec3; 4], [glam::Vec2; 4]),
+    {
+        let atlas = match self.get_atlas(size) {
+            Some(atlas) => atlas,
+            None => return,
+        };
 
-        for c in text.chars() {
-            if c.is_control() {
-                if c == '\n' {
-                    x_pos = 0.0;
-                    y_pos += (atlas.line_spacing >> 6) as f32;
-                    continue;
-                }
+        for glyph in layout.lines.iter().flat_map(|line| &line.glyphs) {
+            if let Some((texture, vertices, texture_coords)) =
+                self.glyph_quad(atlas, glyph, glam::Vec2::ZERO, size)
+            {
+                let vertices = vertices.map(|vertex| {
+                    transform
+                        .transform_point2(vertex.truncate())
+                        .extend(vertex.z)
+                });
+                render(texture, vertices, texture_coords);
             }
+        }
+    }
 
-            if c.is_whitespace() {}
+    pub(crate) fn glyph_quads(
+        &self,
+        layout: &TextLayout,
+        transform: glam::Affine2,
+        size: u32,
+    ) -> Vec<GlyphQuad> {
+        let atlas = match self.get_atlas(size) {
+            Some(atlas) => atlas,
+            None => return Vec::new(),
+        };
 
-            let character = &atlas.characters[&c];
+        layout
+            .lines
+            .iter()
+            .flat_map(|line| &line.glyphs)
+            .filter_map(|glyph| {
+                let (_, vertices, _) = self.glyph_quad(atlas, glyph, glam::Vec2::ZERO, size)?;
+                let character = atlas.glyph(glyph.glyph);
+                Some(GlyphQuad {
+                    page: character.page,
+                    vertices: vertices.map(|vertex| {
+                        transform
+                            .transform_point2(vertex.truncate())
+                            .extend(vertex.z)
+                    }),
+                    region: character.region,
+                })
+            })
+            .collect()
+    }
 
-            // TODO: Should it bearing the first caractar?
-            let xpos = x_pos + character.bearing.x as f32 * scale;
-            let ypos = y_pos + (h_bearing_y - character.bearing.y) as f32 * scale;
+    pub(crate) fn page_texture(&self, size: u32, page: usize) -> Option<&Rc<Texture>> {
+        self.atlas
+            .get(&self.atlas_size(size))?
+            .pages
+            .get(page)?
+            .texture
+            .as_ref()
+    }
 
-            render(
-                &atlas.texture,
-                glam::vec2(xpos, ypos),
-                character.sub_texture.region,
-            );
-            x_pos += (character.advance >> 6) as f32 * scale;
-        }
+    // Quad of a single laid out glyph, None when the glyph is empty or the size isn't built
+    pub(crate) fn glyph_vertices(
+        &self,
+        glyph: &PositionedGlyph,
+        position: glam::Vec2,
+        size: u32,
+    ) -> Option<(&Rc<Texture>, [glam::Vec3; 4], [glam::Vec2; 4])> {
+        let atlas = self.atlas.get(&self.atlas_size(size))?;
+        self.glyph_quad(atlas, glyph, position, size)
     }
 
-    pub fn draw_vertices<F>(&self, text: &str, position: glam::Vec2, size: u32, mut render: F)
-    where
-        F: FnMut(&Rc<Texture>, [glam::Vec3; 4], [glam::Vec2; 4]),
-    {
-        if !self.atlas.contains_key(&size) {
-            warn!("Font should be build before");
-            return;
+    fn glyph_quad<'a>(
+        &self,
+        atlas: &'a FontAtlas,
+        glyph: &PositionedGlyph,
+        position: glam::Vec2,
+        size: u32,
+    ) -> Option<(&'a Rc<Texture>, [glam::Vec3; 4], [glam::Vec2; 4])> {
+        let character = atlas.glyph(glyph.glyph);
+        if character.size.x == 0 || character.size.y == 0 {
+            return None;
         }
 
-        let atlas = &self.atlas[&size];
-        let scale = 1.0;
-        let mut x_pos = position.x;
-        let mut y_pos = position.y;
-
-        let h_bearing_y = &atlas.characters[&'H'].bearing.y;
-
-        for c in text.chars() {
-            if c.is_control() {
-                if c == '\n' {
-                    x_pos = position.x;
-                    y_pos += (atlas.line_spacing >> 6) as f32;
-                    continue;
-                }
-            }
+        let scale = self.scale(size);
+        let xpos = position.x + glyph.position.x + character.bearing.x as f32 * scale;
+        let ypos = position.y + glyph.position.y - character.bearing.y as f32 * scale;
+        let w = character.size.x as f32 * scale;
+        let h = character.size.y as f32 * scale;
+
+        // Same corner order as the texture coords
+        #[rustfmt::skip]
+        let vertices: [glam::Vec3; 4] = [
+            glam::vec3(xpos + w , ypos      , 0.0),
+            glam::vec3(xpos     , ypos      , 0.0),
+            glam::vec3(xpos     , ypos + h  , 0.0),
+            glam::vec3(xpos + w , ypos + h  , 0.0),
+        ];
+
+        Some((
+            atlas.texture(character),
+            vertices,
+            atlas.texture_coords(character),
+        ))
+    }
+}
 
-            if c.is_whitespace() {}
-
-            let character = &atlas.characters[&c];
-
-            // TODO: Should it bearing the first caractar?
-            let xpos = x_pos + character.bearing.x as f32 * scale;
-            let ypos = y_pos + (h_bearing_y - character.bearing.y) as f32 * scale;
-
-            let w = character.size.x as f32 * scale;
-            let h = character.size.y as f32 * scale;
-
-            // TODO: This could also be cached someday!
-            #[rustfmt::skip]
-            let vertices: [glam::Vec3; 4] = [
-                glam::vec3(xpos + w , ypos + h  , 0.0),
-                glam::vec3(xpos + w , ypos      , 0.0),
-                glam::vec3(xpos     , ypos      , 0.0),
-                glam::vec3(xpos     , ypos + h  , 0.0),
-            ];
-
-            render(
-                &atlas.texture,
-                vertices,
-                character
-                    .sub_texture
-                    .texture_coords
-                    .unwrap_or(crate::render::vertex::TEXTURE_COORDS)
-                    .clone(),
-            );
-            x_pos += (character.advance >> 6) as f32 * scale;
+// Splits the text in runs of the first font that has each character, whitespace stays in
+// the current run. Characters that no font has are drawn with the first font
+pub(crate) fn fallback_runs(text: &str, fonts: &[&Font]) -> Vec<(usize, String)> {
+    let mut runs: Vec<(usize, String)> = Vec::new();
+    for c in text.chars() {
+        let font = if c.is_whitespace() || c.is_control() {
+            runs.last().map_or(0, |(font, _)| *font)
+        } else {
+            fonts.iter().position(|font| font.has_char(c)).unwrap_or(0)
+        };
+        match runs.last_mut() {
+            Some((run_font, run)) if *run_font == font => run.push(c),
+            _ => runs.push((font, c.to_string())),
         }
     }
+    runs
+}
+
+// Always cached so the layout can measure the common characters and the ellipsis,
+// which can shape differently on its own
+fn ascii() -> String {
+    let mut text = (32..127u8).map(char::from).collect::<String>();
+    text.push('\n');
+    text.push_str(layout::ELLIPSIS);
+    text
 }
@@ -0,0 +1,189 @@
+use image::{GrayImage, Luma};
+
+use super::Font;
+
+// Glyphs of distance field fonts are rasterized once at this size and scaled when drawn
+pub(crate) const SDF_SIZE: u32 = 48;
+// Distance in atlas pixels covered by the field on each side of the outline, it limits
+// the width of the outline, glow and shadow softness
+pub(crate) const SDF_SPREAD: u32 = 8;
+
+// Far enough to never win against a real distance, but small enough to avoid infinities
+const FAR: f32 = 1e20;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TextOutline {
+    // In pixels of the drawn text
+    pub width: f32,
+    pub color: glam::Vec4,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TextGlow {
+    pub radius: f32,
+    pub color: glam::Vec4,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TextShadow {
+    pub offset: glam::Vec2,
+    pub color: glam::Vec4,
+    // Blur of the edge, only used by distance field fonts
+    pub softness: f32,
+}
+
+// The outline and the glow need a distance field font, the shadow is drawn with any font
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextEffects {
+    pub outline: Option<TextOutline>,
+    pub glow: Option<TextGlow>,
+    pub shadow: Option<TextShadow>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum TextPass {
+    Shadow,
+    Glow,
+    Outline,
+    Fill,
+}
+
+impl TextPass {
+    // Drawn back to front, each pass draws the whole text so the outline of a glyph never
+    // covers its neighbour
+    pub(crate) const ALL: [TextPass; 4] = [
+        TextPass::Shadow,
+        TextPass::Glow,
+        TextPass::Outline,
+        TextPass::Fill,
+    ];
+
+    // Offset, color and distance field params of the pass, None when the pass draws nothing
+    pub(crate) fn params(
+        self,
+        font: &Font,
+        size: u32,
+        effects: &TextEffects,
+        color: glam::Vec4,
+    ) -> Option<(glam::Vec2, glam::Vec4, Option<glam::Vec2>)> {
+        let sdf = font.is_sdf();
+        let distance = |pixels: f32| font.sdf_distance(size, pixels);
+        match self {
+            TextPass::Shadow => effects.shadow.map(|shadow| {
+                let params = glam::vec2(0.5, distance(shadow.softness));
+                (shadow.offset, shadow.color, Some(params).filter(|_| sdf))
+            }),
+            TextPass::Glow => effects.glow.filter(|_| sdf).map(|glow| {
+                let params = glam::vec2(0.5, distance(glow.radius));
+                (glam::Vec2::ZERO, glow.color, Some(params))
+            }),
+            TextPass::Outline => effects.outline.filter(|_| sdf).map(|outline| {
+                let threshold = (0.5 - distance(outline.width)).max(0.0);
+                (
+                    glam::Vec2::ZERO,
+                    outline.color,
+                    Some(glam::vec2(threshold, 0.0)),
+                )
+            }),
+            TextPass::Fill => Some((
+                glam::Vec2::ZERO,
+                color,
+                Some(glam::vec2(0.5, 0.0)).filter(|_| sdf),
+            )),
+        }
+    }
+}
+
+// Distance field of a glyph coverage bitmap, padded by the spread on every side.
+// 0.5 is the outline of the glyph, values above are inside
+pub(crate) fn signed_distance_field(coverage: &GrayImage, spread: u32) -> GrayImage {
+    let width = (coverage.width() + spread * 2) as usize;
+    let height = (coverage.height() + spread * 2) as usize;
+    let inside = |x: usize, y: usize| {
+        let (x, y) = (x as i64 - spread as i64, y as i64 - spread as i64);
+        x >= 0
+            && y >= 0
+            && (x as u32) < coverage.width()
+            && (y as u32) < coverage.height()
+            && coverage.get_pixel(x as u32, y as u32)[0] >= 128
+    };
+
+    let mut to_inside = vec![FAR; width * height];
+    let mut to_outside = vec![FAR; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            if inside(x, y) {
+                to_inside[y * width + x] = 0.0;
+            } else {
+                to_outside[y * width + x] = 0.0;
+            }
+        }
+    }
+    distance_transform(&mut to_inside, width, height);
+    distance_transform(&mut to_outside, width, height);
+
+    GrayImage::from_fn(width as u32, height as u32, |x, y| {
+        let index = y as usize * width + x as usize;
+        // Positive outside of the glyph
+        let distance = to_inside[index].sqrt() - to_outside[index].sqrt();
+        let value = 0.5 - distance / (2.0 * spread as f32);
+        Luma([(value.clamp(0.0, 1.0) * 255.0).round() as u8])
+    })
+}
+
+// Squared euclidean distance to the nearest zero of the grid (Felzenszwalb and Huttenlocher),
+// done on the columns then on the rows
+fn distance_transform(grid: &mut [f32], width: usize, height: usize) {
+    let size = width.max(height);
+    let mut f = vec![0.0; size];
+    let mut d = vec![0.0; size];
+    let mut v = vec![0; size];
+    let mut z = vec![0.0; size + 1];
+
+    for x in 0..width {
+        for (y, value) in f[..height].iter_mut().enumerate() {
+            *value = grid[y * width + x];
+        }
+        distance_transform_1d(&f[..height], &mut d, &mut v, &mut z);
+        for (y, value) in d[..height].iter().enumerate() {
+            grid[y * width + x] = *value;
+        }
+    }
+    for y in 0..height {
+        f[..width].copy_from_slice(&grid[y * width..(y + 1) * width]);
+        distance_transform_1d(&f[..width], &mut d, &mut v, &mut z);
+        grid[y * width..(y + 1) * width].copy_from_slice(&d[..width]);
+    }
+}
+
+// Lower envelope of the parabolas rooted at each sample
+fn distance_transform_1d(f: &[f32], d: &mut [f32], v: &mut [usize], z: &mut [f32]) {
+    let intersection = |q: usize, p: usize| {
+        ((f[q] + (q * q) as f32) - (f[p] + (p * p) as f32)) / (2.0 * q as f32 - 2.0 * p as f32)
+    };
+
+    let mut k = 0;
+    v[0] = 0;
+    z[0] = -FAR;
+    z[1] = FAR;
+    for q in 1..f.len() {
+        let mut s = intersection(q, v[k]);
+        while s <= z[k] {
+            k -= 1;
+            s = intersection(q, v[k]);
+        }
+        k += 1;
+        v[k] = q;
+        z[k] = s;
+        z[k + 1] = FAR;
+    }
+
+    k = 0;
+    for (q, distance) in d.iter_mut().enumerate().take(f.len()) {
+        while z[k + 1] < q as f32 {
+            k += 1;
+        }
+        let offset = q as f32 - v[k] as f32;
+        *distance = offset * offset + f[v[k]];
+    }
+}
@@ -0,0 +1,179 @@
+#[cfg(feature = "shaping")]
+use super::FontSource;
+use super::{Font, FontAtlas};
+
+// A glyph of a paragraph in logical order
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ShapedGlyph {
+    pub glyph: u32,
+    // First character of the cluster the glyph was shaped from
+    pub character: char,
+    pub advance: f32,
+    // Added to the pen position, y goes down
+    pub offset: glam::Vec2,
+    // Bidi embedding level, odd levels are right to left
+    pub level: u8,
+    // Markup span the glyph belongs to, 0 for plain text
+    pub span: usize,
+}
+
+impl Font {
+    // Glyphs the text is drawn with, used to cache them before the text is laid out
+    #[cfg(not(feature = "shaping"))]
+    pub(crate) fn glyph_indices(&self, text: &str) -> Vec<u32> {
+        text.chars()
+            .filter(|c| !c.is_control())
+            .map(|c| self.char_index(c))
+            .collect()
+    }
+
+    #[cfg(feature = "shaping")]
+    pub(crate) fn glyph_indices(&self, text: &str) -> Vec<u32> {
+        let data = match &self.source {
+            FontSource::FreeType { data, .. } => data,
+            // Bitmap fonts can't be shaped, and their glyphs are never cached
+            FontSource::Bitmap(_) => return Vec::new(),
+        };
+        text.split('\n')
+            .flat_map(|paragraph| shape_runs(data, paragraph, 1.0))
+            .map(|glyph| glyph.glyph)
+            .collect()
+    }
+
+    // Shapes a single paragraph, the text must not contain line breaks
+    #[cfg(not(feature = "shaping"))]
+    pub(crate) fn shape(&self, atlas: &FontAtlas, paragraph: &str, size: u32) -> Vec<ShapedGlyph> {
+        self.shape_kerned(atlas, paragraph, size)
+    }
+
+    #[cfg(feature = "shaping")]
+    pub(crate) fn shape(&self, atlas: &FontAtlas, paragraph: &str, size: u32) -> Vec<ShapedGlyph> {
+        match &self.source {
+            FontSource::FreeType { face, data } => {
+                let scale = size as f32 / face.em_size().max(1) as f32;
+                shape_runs(data, paragraph, scale)
+            }
+            FontSource::Bitmap(_) => self.shape_kerned(atlas, paragraph, size),
+        }
+    }
+
+    // One glyph per character, placed with the advances and the kerning of the font
+    fn shape_kerned(&self, atlas: &FontAtlas, paragraph: &str, size: u32) -> Vec<ShapedGlyph> {
+        let mut glyphs: Vec<ShapedGlyph> = Vec::with_capacity(paragraph.len());
+        for c in paragraph.chars().filter(|c| !c.is_control()) {
+            let glyph = self.char_index(c);
+            if let Some(previous) = glyphs.last_mut() {
+                previous.advance += self.kerning(previous.glyph, glyph, size);
+            }
+
+            glyphs.push(ShapedGlyph {
+                glyph,
+                character: c,
+                advance: (atlas.glyph(glyph).advance >> 6) as f32,
+                offset: glam::Vec2::ZERO,
+                level: 0,
+                span: 0,
+            });
+        }
+        glyphs
+    }
+}
+
+// Splits the paragraph in bidi runs and shapes each of them, scale converts font units
+// to pixels
+#[cfg(feature = "shaping")]
+fn shape_runs(data: &[u8], paragraph: &str, scale: f32) -> Vec<ShapedGlyph> {
+    let face = match rustybuzz::Face::from_slice(data, 0) {
+        Some(face) => face,
+        None => {
+            log::warn!("The font can't be shaped");
+            return Vec::new();
+        }
+    };
+
+    let mut glyphs = Vec::new();
+    for (range, level) in bidi_runs(paragraph) {
+        let text = &paragraph[range.clone()];
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.set_direction(if level % 2 == 1 {
+            rustybuzz::Direction::RightToLeft
+        } else {
+            rustybuzz::Direction::LeftToRight
+        });
+        buffer.guess_segment_properties();
+        let output = rustybuzz::shape(&face, &[], buffer);
+
+        let mut run = output
+            .glyph_infos()
+            .iter()
+            .zip(output.glyph_positions())
+            .map(|(info, position)| ShapedGlyph {
+                glyph: info.glyph_id,
+                character: text[info.cluster as usize..].chars().next().unwrap_or(' '),
+                advance: position.x_advance as f32 * scale,
+                offset: glam::vec2(
+                    position.x_offset as f32 * scale,
+                    -position.y_offset as f32 * scale,
+                ),
+                level,
+                span: 0,
+            })
+            .filter(|glyph| !glyph.character.is_control())
+            .collect::<Vec<_>>();
+        // Right to left runs come out in visual order
+        if level % 2 == 1 {
+            run.reverse();
+        }
+        glyphs.extend(run);
+    }
+    glyphs
+}
+
+// Byte ranges of the paragraph with the same embedding level
+#[cfg(feature = "shaping")]
+fn bidi_runs(paragraph: &str) -> Vec<(std::ops::Range<usize>, u8)> {
+    let bidi_info = unicode_bidi::BidiInfo::new(paragraph, None);
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for (index, _) in paragraph.char_indices().skip(1) {
+        if bidi_info.levels[index] != bidi_info.levels[start] {
+            runs.push((start..index, bidi_info.levels[start].number()));
+            start = index;
+        }
+    }
+    if start < paragraph.len() {
+        runs.push((start..paragraph.len(), bidi_info.levels[start].number()));
+    }
+    runs
+}
+
+// Reverses the right to left sequences of a line into visual order (rule L2 of the
+// Unicode bidi algorithm)
+pub(crate) fn reorder_line(glyphs: &mut [ShapedGlyph]) {
+    let highest = glyphs.iter().map(|glyph| glyph.level).max().unwrap_or(0);
+    let lowest_odd = match glyphs
+        .iter()
+        .map(|glyph| glyph.level)
+        .filter(|level| level % 2 == 1)
+        .min()
+    {
+        Some(level) => level,
+        None => return,
+    };
+
+    for level in (lowest_odd..=highest).rev() {
+        let mut index = 0;
+        while index < glyphs.len() {
+            if glyphs[index].level < level {
+                index += 1;
+                continue;
+            }
+            let start = index;
+            while index < glyphs.len() && glyphs[index].level >= level {
+                index += 1;
+            }
+            glyphs[start..index].reverse();
+        }
+    }
+}
@@ -1,5 +1,6 @@
-use super::label::Label;
+use super::{label::Label, Skin};
 use crate::{
+    core::asset_manager::AssetManager,
     font::Font,
     render::{renderer::Renderer, RenderQuad},
     shapes::rectangle::Rect,
@@ -15,6 +16,7 @@ pub(crate) struct Button {
     pub(crate) normal_color: glam::Vec4,
     pub(crate) selected_color: glam::Vec4,
     pub(crate) disabled_color: glam::Vec4,
+    pub(crate) skin: Option<Skin>,
 }
 
 impl Button {
@@ -24,21 +26,38 @@ impl Button {
             normal_color: color,
             selected_color: color + glam::vec4(0.05, 0.05, 0.05, 0.0),
             disabled_color: color - glam::vec4(0.05, 0.05, 0.05, 0.0),
+            skin: None,
         }
     }
 
-    pub(crate) fn draw(&self, renderer: &mut RefMut<Renderer>, rect: Rect, font: &Rc<Font>) {
-        renderer.draw_quad(RenderQuad {
-            size: rect.size().into(),
-            position: rect.position().into(),
-            scale: glam::Vec2::ONE,
-            rotate: 0.0,
-            center_origin: false,
-            color: self.normal_color,
-        });
+    pub(crate) fn label(&self) -> &Label {
+        match &self.button_type {
+            ButtonType::Text(label) => label,
+        }
+    }
+
+    pub(crate) fn draw(
+        &self,
+        renderer: &mut RefMut<Renderer>,
+        asset_manager: &AssetManager,
+        rect: Rect,
+        font: &Rc<Font>,
+    ) {
+        match &self.skin {
+            // The skin texture is tinted with the state color
+            Some(skin) => skin.draw(renderer, asset_manager, rect, self.normal_color),
+            None => renderer.draw_quad(RenderQuad {
+                size: rect.size().into(),
+                position: rect.position().into(),
+                scale: glam::Vec2::ONE,
+                rotate: 0.0,
+                center_origin: false,
+                color: self.normal_color,
+            }),
+        }
 
         match &self.button_type {
-            ButtonType::Text(label) => label.draw(renderer, rect, font),
+            ButtonType::Text(label) => label.draw(renderer, asset_manager, rect, font),
         }
     }
 }
@@ -1,7 +1,7 @@
 use std::{cell::RefMut, rc::Rc};
 
 use crate::{
-    core::engine::EngineTimerView,
+    core::{asset_manager::AssetManager, engine::EngineTimerView, localization::Argument},
     font::Font,
     render::{renderer::Renderer, RenderQuad},
     shapes::rectangle::Rect,
@@ -10,7 +10,8 @@ use crate::{
 use super::{
     button::ButtonType,
     label::{Label, Orientation},
-    Button, Constraints, Elements, Panel, Screen,
+    text_input::{TextEdit, TextField, TextInput},
+    Button, Constraints, Elements, Panel, Screen, Skin,
 };
 
 pub struct Group {
@@ -19,6 +20,10 @@ pub struct Group {
     width: Constraints,
     height: Constraints,
     elements: Vec<Elements>,
+    // Typed since the last frame, taken by the focused text input
+    pub(crate) edits: Vec<TextEdit>,
+    pub(crate) copied: Option<String>,
+    pub(crate) has_focus: bool,
 }
 
 impl Group {
@@ -29,6 +34,9 @@ impl Group {
             width: Constraints::Auto,
             height: Constraints::Auto,
             elements: Vec::new(),
+            edits: Vec::new(),
+            copied: None,
+            has_focus: false,
         }
     }
 
@@ -48,6 +56,19 @@ impl Group {
         self.elements.push(Elements::Panel(panel));
     }
 
+    pub fn panel_with_skin(
+        &mut self,
+        x: Constraints,
+        y: Constraints,
+        width: Constraints,
+        height: Constraints,
+        skin: Skin,
+    ) {
+        let mut panel = Panel::new(x, y, width, height);
+        panel.skin = Some(skin);
+        self.elements.push(Elements::Panel(panel));
+    }
+
     pub fn print_diagnostics(&mut self) {
         self.elements.push(Elements::Diagnostics);
     }
@@ -72,9 +93,69 @@ impl Group {
         false
     }
 
+    // The text is resolved with AssetManager::localize when the group is drawn
+    pub fn label_localized(&mut self, key: &str, args: &[(&str, Argument)]) {
+        let label =
+            Label::new_localized(key, args, glam::vec4(1.0, 1.0, 1.0, 1.0), Orientation::Left);
+        self.elements.push(Elements::Label(label));
+    }
+
+    pub fn button_localized(&mut self, key: &str, args: &[(&str, Argument)]) -> bool {
+        let label = Label::new_localized(
+            key,
+            args,
+            glam::vec4(1.0, 1.0, 1.0, 1.0),
+            Orientation::Center,
+        );
+        let button = Button::new(ButtonType::Text(label), glam::vec4(0.01, 0.01, 0.01, 1.0));
+        self.elements.push(Elements::Button(button));
+        false
+    }
+
+    // Applies the text typed since the last frame when the input is focused, keyboard events
+    // don't reach the scene while a text input has the focus
+    pub fn text_input(&mut self, input: &mut TextInput) {
+        if input.focused {
+            self.has_focus = true;
+            for edit in std::mem::take(&mut self.edits) {
+                if let Some(copied) = input.apply(&edit) {
+                    self.copied = Some(copied);
+                }
+            }
+        }
+        self.elements
+            .push(Elements::TextInput(TextField::new(input)));
+    }
+
+    pub fn button_with_skin(&mut self, value: &str, skin: Skin) -> bool {
+        let label = Label::new(
+            value.to_string(),
+            glam::vec4(1.0, 1.0, 1.0, 1.0),
+            Orientation::Center,
+        );
+        let mut button = Button::new(ButtonType::Text(label), glam::Vec4::ONE);
+        button.skin = Some(skin);
+        self.elements.push(Elements::Button(button));
+        false
+    }
+
+    // Texts the group draws, their glyphs are cached before drawing
+    pub(crate) fn texts(&self, asset_manager: &AssetManager) -> Vec<String> {
+        self.elements
+            .iter()
+            .filter_map(|element| match element {
+                Elements::Label(label) => Some(label.resolve(asset_manager)),
+                Elements::Button(button) => Some(button.label().resolve(asset_manager)),
+                Elements::TextInput(field) => Some(field.text.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
     pub(crate) fn render(
         &self,
         renderer: &mut RefMut<Renderer>,
+        asset_manager: &AssetManager,
         view_time: &EngineTimerView,
         font: &Rc<Font>,
     ) {
@@ -84,7 +165,7 @@ impl Group {
         let (position, size) = match &self.elements.first() {
             Some(element) => match &element {
                 Elements::Panel(panel) => {
-                    panel.draw(renderer, spacing, elements_count);
+                    panel.draw(renderer, spacing, elements_count, asset_manager);
                     (panel.position(), panel.size(spacing, elements_count))
                 }
                 Elements::Screen(screen) => (screen.position(), glam::Vec2::ZERO),
@@ -104,6 +185,7 @@ impl Group {
                     )
                     .draw(
                         renderer,
+                        asset_manager,
                         Rect::from_position_size(
                             element_position.into(),
                             glam::vec2(size.x - padding as f32 * 2.0, spacing as f32).into(),
@@ -118,6 +200,7 @@ impl Group {
                     )
                     .draw(
                         renderer,
+                        asset_manager,
                         Rect::from_position_size(
                             element_position.into(),
                             glam::vec2(size.x - padding as f32 * 2.0, spacing as f32).into(),
@@ -129,6 +212,7 @@ impl Group {
                 Elements::Label(label) => {
                     label.draw(
                         renderer,
+                        asset_manager,
                         Rect::from_position_size(
                             element_position.into(),
                             glam::vec2(size.x - padding as f32 * 2.0, spacing as f32).into(),
@@ -140,6 +224,19 @@ impl Group {
                 Elements::Button(button) => {
                     button.draw(
                         renderer,
+                        asset_manager,
+                        Rect::from_position_size(
+                            element_position.into(),
+                            glam::vec2(size.x - padding as f32 * 2.0, spacing as f32 + 10.0).into(),
+                        ),
+                        font,
+                    );
+                    element_position.y += spacing as f32 + 20.0;
+                }
+                Elements::TextInput(field) => {
+                    field.draw(
+                        renderer,
+                        asset_manager,
                         Rect::from_position_size(
                             element_position.into(),
                             glam::vec2(size.x - padding as f32 * 2.0, spacing as f32 + 10.0).into(),
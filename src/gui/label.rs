@@ -1,10 +1,15 @@
 use crate::{
+    core::{asset_manager::AssetManager, localization::Argument},
+    font::layout::{TextAlign, TextLayoutOptions, VerticalAlign},
+    font::sdf::TextEffects,
     font::Font,
     render::{renderer::Renderer, RenderQuad, RenderText},
     shapes::rectangle::Rect,
 };
 use std::{cell::RefMut, rc::Rc};
 
+pub(crate) const TEXT_SIZE: u32 = 25;
+
 pub(crate) enum Orientation {
     Center,
     Left,
@@ -15,6 +20,10 @@ pub(crate) struct Label {
     pub(crate) text: String,
     pub(crate) color: glam::Vec4,
     pub(crate) orientation: Orientation,
+    // When set the text is a localization key
+    pub(crate) args: Option<Vec<(String, Argument)>>,
+    // Long text is cut at the rect width instead of wrapped
+    pub(crate) wrap: bool,
 }
 
 impl Label {
@@ -23,26 +32,64 @@ impl Label {
             text,
             color,
             orientation,
+            args: None,
+            wrap: true,
+        }
+    }
+
+    pub(crate) fn new_localized(
+        key: &str,
+        args: &[(&str, Argument)],
+        color: glam::Vec4,
+        orientation: Orientation,
+    ) -> Label {
+        let args = args
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+        Label {
+            args: Some(args),
+            ..Label::new(key.to_string(), color, orientation)
+        }
+    }
+
+    // The string of the current language for localized labels
+    pub(crate) fn resolve(&self, asset_manager: &AssetManager) -> String {
+        match &self.args {
+            Some(args) => asset_manager.get_localization().localize(&self.text, args),
+            None => self.text.clone(),
         }
     }
 
-    pub(crate) fn draw(&self, renderer: &mut RefMut<Renderer>, rect: Rect, font: &Rc<Font>) {
-        let size = font.measure(&self.text, 25) / 2.0;
-        let rect_size: glam::Vec2 = rect.size().into();
-        let rect_size = rect_size / 2.0;
-        let position: glam::Vec2 = rect.position().into();
-        let position = match self.orientation {
-            Orientation::Center => position + rect_size - size,
-            Orientation::Left => position,
-            Orientation::Right => todo!(),
+    pub(crate) fn draw(
+        &self,
+        renderer: &mut RefMut<Renderer>,
+        asset_manager: &AssetManager,
+        rect: Rect,
+        font: &Rc<Font>,
+    ) {
+        let text = self.resolve(asset_manager);
+        let (align, vertical_align) = match self.orientation {
+            Orientation::Center => (TextAlign::Center, VerticalAlign::Middle),
+            Orientation::Left => (TextAlign::Left, VerticalAlign::Top),
+            Orientation::Right => (TextAlign::Right, VerticalAlign::Top),
         };
         renderer.draw_text(RenderText {
-            text: &self.text,
+            text: &text,
             font: font.clone(),
-            size: 25,
-            position,
+            size: TEXT_SIZE,
+            position: rect.position().into(),
             scale: glam::Vec2::ONE,
             color: glam::vec4(1.0, 1.0, 1.0, 1.0),
+            options: TextLayoutOptions {
+                max_width: Some(rect.width),
+                max_height: Some(rect.height),
+                align,
+                vertical_align,
+                wrap: self.wrap,
+                ..Default::default()
+            },
+            effects: TextEffects::default(),
         });
     }
 }
@@ -1,10 +1,19 @@
-use self::{button::Button, label::Label, panel::Panel, screen::Screen, texture::Texture};
+use self::{
+    button::Button, label::Label, panel::Panel, screen::Screen, text_input::TextField,
+    texture::Texture,
+};
+
+pub use self::{skin::Skin, text_input::TextInput};
+
+pub(crate) use self::label::TEXT_SIZE;
 
 mod button;
 pub mod group;
 mod label;
 mod panel;
 mod screen;
+mod skin;
+pub(crate) mod text_input;
 mod texture;
 
 pub enum Constraints {
@@ -22,4 +31,5 @@ pub(crate) enum Elements {
     Texture(Texture),
     Label(Label),
     Button(Button),
+    TextInput(TextField),
 }
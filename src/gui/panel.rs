@@ -1,14 +1,19 @@
 use std::cell::RefMut;
 
-use crate::render::{renderer::Renderer, RenderQuad};
+use crate::{
+    core::asset_manager::AssetManager,
+    render::{renderer::Renderer, RenderQuad},
+    shapes::rectangle::Rect,
+};
 
-use super::Constraints;
+use super::{Constraints, Skin};
 
 pub(crate) struct Panel {
     x: Constraints,
     y: Constraints,
     width: Constraints,
     height: Constraints,
+    pub(crate) skin: Option<Skin>,
 }
 
 impl Panel {
@@ -23,6 +28,7 @@ impl Panel {
             y,
             width,
             height,
+            skin: None,
         }
     }
 
@@ -61,10 +67,19 @@ impl Panel {
         renderer: &mut RefMut<Renderer>,
         spacing: i32,
         elements_count: i32,
-        // font: &Font,
+        asset_manager: &AssetManager,
     ) {
         let position = self.position();
         let size = self.size(spacing, elements_count);
+        if let Some(skin) = &self.skin {
+            skin.draw(
+                renderer,
+                asset_manager,
+                Rect::from_position_size(position.into(), size.into()),
+                glam::Vec4::ONE,
+            );
+            return;
+        }
         renderer.draw_quad(RenderQuad {
             size,
             position,
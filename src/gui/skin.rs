@@ -0,0 +1,63 @@
+use std::cell::RefMut;
+
+use crate::{
+    core::asset_manager::{AssetManager, TextureId},
+    render::{
+        nine_slice::{nine_slice_quads, NineSlice},
+        renderer::Renderer,
+        RenderVertices,
+    },
+    shapes::rectangle::Rect,
+};
+
+// Nine-slice texture drawn behind panels and buttons
+#[derive(Debug, Clone)]
+pub struct Skin {
+    pub texture_id: TextureId,
+    // Region of the texture in pixels, the whole texture when None
+    pub region: Option<Rect>,
+    pub nine_slice: NineSlice,
+}
+
+impl Skin {
+    pub fn new(texture_id: TextureId, nine_slice: NineSlice) -> Skin {
+        Skin {
+            texture_id,
+            region: None,
+            nine_slice,
+        }
+    }
+
+    pub fn with_region(mut self, region: Rect) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    pub(crate) fn draw(
+        &self,
+        renderer: &mut RefMut<Renderer>,
+        asset_manager: &AssetManager,
+        rect: Rect,
+        color: glam::Vec4,
+    ) {
+        let texture = asset_manager.get_texture(&self.texture_id);
+        let texture_size = texture.size().as_vec2();
+        let region = self
+            .region
+            .unwrap_or(Rect::new(0.0, 0.0, texture_size.x, texture_size.y));
+        let position: glam::Vec2 = rect.position().into();
+
+        for quad in nine_slice_quads(&self.nine_slice, rect.size().into(), region, texture_size) {
+            renderer.draw_vertices(RenderVertices {
+                texture: Some(texture.clone()),
+                normal_texture: None,
+                vertices: quad.positions.map(|vertex| (position + vertex).extend(0.0)),
+                color,
+                texture_coords: quad.texture_coords,
+                lit: false,
+                wrap: false,
+                sdf: None,
+            });
+        }
+    }
+}
@@ -0,0 +1,441 @@
+use std::{cell::RefMut, ops::Range, rc::Rc};
+
+use super::label::{Label, Orientation, TEXT_SIZE};
+use crate::{
+    core::asset_manager::AssetManager,
+    font::Font,
+    render::{renderer::Renderer, RenderQuad},
+    shapes::rectangle::Rect,
+};
+
+// Edits received by the focused text input since the last frame
+#[derive(Debug, Clone)]
+pub(crate) enum TextEdit {
+    Insert(String),
+    Backspace,
+    Delete,
+    // Moves the caret, extending the selection when select is true
+    Left { select: bool },
+    Right { select: bool },
+    Home { select: bool },
+    End { select: bool },
+    SelectAll,
+    Copy,
+    Cut,
+    // The clipboard is read when the shortcut is pressed
+    Paste(String),
+}
+
+// State of an editable field, kept by the scene and passed to Group::text_input every
+// frame. Positions are in characters
+pub struct TextInput {
+    text: String,
+    caret: usize,
+    // Fixed end of the selection, the caret is the moving end
+    anchor: Option<usize>,
+    pub max_length: Option<usize>,
+    pub focused: bool,
+}
+
+impl Default for TextInput {
+    fn default() -> Self {
+        TextInput {
+            text: String::new(),
+            caret: 0,
+            anchor: None,
+            max_length: None,
+            focused: false,
+        }
+    }
+}
+
+impl TextInput {
+    pub fn new() -> TextInput {
+        TextInput::default()
+    }
+
+    pub fn with_max_length(mut self, max_length: usize) -> Self {
+        self.max_length = Some(max_length);
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    // Replaces the text and puts the caret at its end, the text is cut at the max length
+    pub fn set_text(&mut self, text: &str) {
+        self.text.clear();
+        self.caret = 0;
+        self.anchor = None;
+        self.insert(text);
+    }
+
+    pub fn caret(&self) -> usize {
+        self.caret
+    }
+
+    pub fn selection(&self) -> Option<Range<usize>> {
+        self.anchor
+            .filter(|anchor| *anchor != self.caret)
+            .map(|anchor| anchor.min(self.caret)..anchor.max(self.caret))
+    }
+
+    pub fn selected_text(&self) -> Option<&str> {
+        self.selection()
+            .map(|range| &self.text[self.byte_index(range.start)..self.byte_index(range.end)])
+    }
+
+    pub fn focus(&mut self) {
+        self.focused = true;
+    }
+
+    pub fn blur(&mut self) {
+        self.focused = false;
+        self.anchor = None;
+    }
+
+    // Returns the text to put in the clipboard for copy and cut
+    pub(crate) fn apply(&mut self, edit: &TextEdit) -> Option<String> {
+        let length = self.text.chars().count();
+        match edit {
+            TextEdit::Insert(text) | TextEdit::Paste(text) => self.insert(text),
+            TextEdit::Backspace => {
+                if !self.delete_selection() && self.caret > 0 {
+                    self.remove(self.caret - 1..self.caret);
+                }
+            }
+            TextEdit::Delete => {
+                if !self.delete_selection() && self.caret < length {
+                    self.remove(self.caret..self.caret + 1);
+                }
+            }
+            TextEdit::Left { select } => {
+                let target = match (self.selection(), select) {
+                    (Some(selection), false) => selection.start,
+                    _ => self.caret.saturating_sub(1),
+                };
+                self.move_caret(target, *select);
+            }
+            TextEdit::Right { select } => {
+                let target = match (self.selection(), select) {
+                    (Some(selection), false) => selection.end,
+                    _ => (self.caret + 1).min(length),
+                };
+                self.move_caret(target, *select);
+            }
+            TextEdit::Home { select } => self.move_caret(0, *select),
+            TextEdit::End { select } => self.move_caret(length, *select),
+            TextEdit::SelectAll => {
+                self.anchor = Some(0);
+                self.caret = length;
+            }
+            TextEdit::Copy => return self.selected_text().map(str::to_string),
+            TextEdit::Cut => {
+                let copied = self.selected_text().map(str::to_string);
+                self.delete_selection();
+                return copied;
+            }
+        }
+        None
+    }
+
+    // Replaces the selection, control characters are left out and the text is cut at the
+    // max length
+    fn insert(&mut self, text: &str) {
+        self.delete_selection();
+        let length = self.text.chars().count();
+        let available = self
+            .max_length
+            .map_or(usize::MAX, |max_length| max_length.saturating_sub(length));
+        let text = text
+            .chars()
+            .filter(|c| !c.is_control())
+            .take(available)
+            .collect::<String>();
+
+        let index = self.byte_index(self.caret);
+        self.text.insert_str(index, &text);
+        self.caret += text.chars().count();
+    }
+
+    fn delete_selection(&mut self) -> bool {
+        match self.selection() {
+            Some(selection) => {
+                self.remove(selection);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove(&mut self, range: Range<usize>) {
+        let bytes = self.byte_index(range.start)..self.byte_index(range.end);
+        self.text.replace_range(bytes, "");
+        self.caret = range.start;
+        self.anchor = None;
+    }
+
+    fn move_caret(&mut self, caret: usize, select: bool) {
+        if select {
+            self.anchor.get_or_insert(self.caret);
+        } else {
+            self.anchor = None;
+        }
+        self.caret = caret;
+    }
+
+    fn byte_index(&self, index: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(index)
+            .map_or(self.text.len(), |(byte, _)| byte)
+    }
+}
+
+// What a text input looked like when it was added to the group
+pub(crate) struct TextField {
+    pub(crate) text: String,
+    pub(crate) caret: usize,
+    pub(crate) selection: Option<Range<usize>>,
+    pub(crate) focused: bool,
+}
+
+impl TextField {
+    pub(crate) fn new(input: &TextInput) -> TextField {
+        TextField {
+            text: input.text.clone(),
+            caret: input.caret,
+            selection: input.selection(),
+            focused: input.focused,
+        }
+    }
+
+    pub(crate) fn draw(
+        &self,
+        renderer: &mut RefMut<Renderer>,
+        asset_manager: &AssetManager,
+        rect: Rect,
+        font: &Rc<Font>,
+    ) {
+        // Width of the text up to a character, spaces before the caret count
+        let offset = |index: usize| {
+            let prefix = self.text.chars().take(index).collect::<String>();
+            font.advance(&prefix, TEXT_SIZE)
+        };
+        let quad = |renderer: &mut RefMut<Renderer>, x: f32, width: f32, color: glam::Vec4| {
+            renderer.draw_quad(RenderQuad {
+                size: glam::vec2(width, rect.height),
+                position: glam::vec2(rect.x + x, rect.y),
+                scale: glam::Vec2::ONE,
+                rotate: 0.0,
+                center_origin: false,
+                color,
+            })
+        };
+
+        let background = if self.focused { 0.05 } else { 0.01 };
+        quad(
+            renderer,
+            0.0,
+            rect.width,
+            glam::vec4(background, background, background, 1.0),
+        );
+        if let Some(selection) = &self.selection {
+            let start = offset(selection.start);
+            quad(
+                renderer,
+                start,
+                offset(selection.end) - start,
+                glam::vec4(0.2, 0.3, 0.6, 1.0),
+            );
+        }
+        // Stays on one line like the caret offsets
+        Label {
+            wrap: false,
+            ..Label::new(
+                self.text.clone(),
+                glam::vec4(1.0, 1.0, 1.0, 1.0),
+                Orientation::Left,
+            )
+        }
+        .draw(renderer, asset_manager, rect, font);
+        if self.focused {
+            quad(
+                renderer,
+                offset(self.caret),
+                2.0,
+                glam::vec4(1.0, 1.0, 1.0, 1.0),
+            );
+        }
+    }
+}
+
+// The system clipboard when the clipboard feature is enabled, otherwise a clipboard only
+// shared by the text inputs of the game
+pub(crate) struct Clipboard {
+    #[cfg(feature = "clipboard")]
+    system: Option<arboard::Clipboard>,
+    text: String,
+}
+
+impl Clipboard {
+    pub(crate) fn new() -> Clipboard {
+        Clipboard {
+            #[cfg(feature = "clipboard")]
+            system: arboard::Clipboard::new()
+                .map_err(|error| log::warn!("System clipboard unavailable: {}", error))
+                .ok(),
+            text: String::new(),
+        }
+    }
+
+    pub(crate) fn get_text(&mut self) -> String {
+        #[cfg(feature = "clipboard")]
+        if let Some(text) = self
+            .system
+            .as_mut()
+            .and_then(|system| system.get_text().ok())
+        {
+            return text;
+        }
+        self.text.clone()
+    }
+
+    pub(crate) fn set_text(&mut self, text: String) {
+        #[cfg(feature = "clipboard")]
+        if let Some(system) = self.system.as_mut() {
+            if let Err(error) = system.set_text(text.clone()) {
+                log::warn!("Could not copy to the system clipboard: {}", error);
+            }
+        }
+        self.text = text;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(text: &str) -> TextInput {
+        let mut input = TextInput::new();
+        input.set_text(text);
+        input
+    }
+
+    fn apply(input: &mut TextInput, edits: &[TextEdit]) {
+        for edit in edits {
+            input.apply(edit);
+        }
+    }
+
+    #[test]
+    fn caret_moves_by_characters() {
+        let mut input = input("aé🙂b");
+        assert_eq!(input.caret(), 4);
+
+        apply(
+            &mut input,
+            &[TextEdit::Left { select: false }, TextEdit::Backspace],
+        );
+        assert_eq!((input.text(), input.caret()), ("aéb", 2));
+
+        apply(&mut input, &[TextEdit::Delete]);
+        assert_eq!((input.text(), input.caret()), ("aé", 2));
+
+        apply(
+            &mut input,
+            &[
+                TextEdit::Home { select: false },
+                TextEdit::Right { select: false },
+                TextEdit::Insert(String::from("ß")),
+            ],
+        );
+        assert_eq!((input.text(), input.caret()), ("aßé", 2));
+
+        // The caret stays in the text
+        apply(
+            &mut input,
+            &[TextEdit::Home { select: false }, TextEdit::Backspace],
+        );
+        apply(&mut input, &[TextEdit::Left { select: false }]);
+        assert_eq!(input.caret(), 0);
+        apply(
+            &mut input,
+            &[TextEdit::End { select: false }, TextEdit::Delete],
+        );
+        apply(&mut input, &[TextEdit::Right { select: false }]);
+        assert_eq!((input.text(), input.caret()), ("aßé", 3));
+    }
+
+    #[test]
+    fn selects_multibyte_characters() {
+        let mut input = input("日本語テキスト");
+        apply(
+            &mut input,
+            &[
+                TextEdit::Home { select: false },
+                TextEdit::Right { select: true },
+                TextEdit::Right { select: true },
+            ],
+        );
+        assert_eq!(input.selection(), Some(0..2));
+        assert_eq!(input.selected_text(), Some("日本"));
+        assert_eq!(input.apply(&TextEdit::Copy), Some(String::from("日本")));
+        assert_eq!(input.text(), "日本語テキスト");
+
+        assert_eq!(input.apply(&TextEdit::Cut), Some(String::from("日本")));
+        assert_eq!((input.text(), input.caret()), ("語テキスト", 0));
+        assert_eq!(input.selection(), None);
+
+        // Selecting backwards, moving without select collapses to the selection start
+        apply(
+            &mut input,
+            &[
+                TextEdit::End { select: false },
+                TextEdit::Left { select: true },
+            ],
+        );
+        assert_eq!(input.selected_text(), Some("ト"));
+        apply(&mut input, &[TextEdit::Left { select: false }]);
+        assert_eq!((input.caret(), input.selection()), (4, None));
+
+        apply(
+            &mut input,
+            &[TextEdit::SelectAll, TextEdit::Insert(String::from("é"))],
+        );
+        assert_eq!((input.text(), input.caret()), ("é", 1));
+        assert_eq!(input.apply(&TextEdit::Copy), None);
+    }
+
+    #[test]
+    fn text_is_cut_at_max_length() {
+        let mut input = TextInput::new().with_max_length(3);
+        input.set_text("añ🙂xyz");
+        assert_eq!((input.text(), input.caret()), ("añ🙂", 3));
+
+        apply(&mut input, &[TextEdit::Insert(String::from("z"))]);
+        assert_eq!((input.text(), input.caret()), ("añ🙂", 3));
+
+        // The selection is removed before the length is checked
+        apply(
+            &mut input,
+            &[
+                TextEdit::Home { select: false },
+                TextEdit::Right { select: true },
+                TextEdit::Paste(String::from("xy")),
+            ],
+        );
+        assert_eq!((input.text(), input.caret()), ("xñ🙂", 1));
+
+        // Control characters don't count
+        apply(
+            &mut input,
+            &[
+                TextEdit::SelectAll,
+                TextEdit::Paste(String::from("é\nü\t!?")),
+            ],
+        );
+        assert_eq!((input.text(), input.caret()), ("éü!", 3));
+    }
+}
@@ -1,3 +1,4 @@
+pub mod nine_slice;
 pub mod render2d_data;
 pub mod render2d_pipeline;
 pub mod renderer;
@@ -7,8 +8,22 @@ pub mod vertex;
 
 use std::rc::Rc;
 
-use self::{texture::Texture, vertex::Vertex};
-use crate::{font::Font, shapes::rectangle::Rect};
+use self::{
+    texture::Texture,
+    vertex::{
+        Instance, Vertex, CENTER_QUAD, TEXTURE_COORDS, TOP_LEFT_QUAD, VERTEX_FLAG_LIT,
+        VERTEX_FLAG_SDF, VERTEX_FLAG_WRAP,
+    },
+};
+use crate::{
+    font::{
+        layout::{TextLayout, TextLayoutOptions},
+        markup::RichSpan,
+        sdf::TextEffects,
+        Font, GlyphQuad,
+    },
+    shapes::rectangle::Rect,
+};
 
 pub struct RenderQuad {
     pub size: glam::Vec2,
@@ -28,6 +43,34 @@ impl RenderQuad {
             Vertex::default(),
         ]
     }
+
+    // Untextured quad, drawn like any other vertices
+    pub fn into_vertices(self) -> RenderVertices {
+        let corners = if self.center_origin {
+            CENTER_QUAD
+        } else {
+            TOP_LEFT_QUAD
+        };
+        let transform = glam::Affine2::from_scale_angle_translation(
+            self.size * self.scale,
+            self.rotate,
+            self.position,
+        );
+        RenderVertices {
+            texture: None,
+            normal_texture: None,
+            vertices: corners.map(|corner| {
+                transform
+                    .transform_point2(corner.truncate().truncate())
+                    .extend(0.0)
+            }),
+            color: self.color,
+            texture_coords: TEXTURE_COORDS,
+            lit: false,
+            wrap: false,
+            sdf: None,
+        }
+    }
 }
 
 pub struct RenderText<'a> {
@@ -37,6 +80,29 @@ pub struct RenderText<'a> {
     pub position: glam::Vec2,
     pub scale: glam::Vec2,
     pub color: glam::Vec4,
+    pub options: TextLayoutOptions,
+    pub effects: TextEffects,
+}
+
+// Glyphs laid out ahead, labels keep them between frames
+pub struct RenderGlyphs<'a> {
+    pub glyphs: &'a [GlyphQuad],
+    pub font: &'a Font,
+    pub size: u32,
+    pub color: glam::Vec4,
+    pub effects: TextEffects,
+}
+
+// Text parsed from markup, each glyph is drawn with the style of its span
+pub struct RenderRichText<'a> {
+    pub spans: &'a [RichSpan],
+    pub layout: &'a TextLayout,
+    pub position: glam::Vec2,
+    pub scale: glam::Vec2,
+    pub rotate: f32,
+    pub effects: TextEffects,
+    // Seconds, drives the glyph animations
+    pub time: f32,
 }
 
 pub struct RenderTexture {
@@ -62,38 +128,123 @@ impl RenderTexture {
 
 pub struct RenderVertices {
     pub texture: Option<Rc<Texture>>,
+    pub normal_texture: Option<Rc<Texture>>,
     pub vertices: [glam::Vec3; 4],
     pub color: glam::Vec4,
     pub texture_coords: [glam::Vec2; 4],
+    pub lit: bool,
+    // Samples the textures with a repeating sampler
+    pub wrap: bool,
+    // Threshold and softness when the texture is a distance field
+    pub sdf: Option<glam::Vec2>,
 }
 
 impl RenderVertices {
     pub fn raw_vertices(&self) -> [Vertex; 4] {
-        [
-            Vertex {
-                position: self.vertices[0],
-                color: self.color,
-                texture_coords: self.texture_coords[0],
-                tex_index: 0,
-            },
-            Vertex {
-                position: self.vertices[1],
-                color: self.color,
-                texture_coords: self.texture_coords[1],
-                tex_index: 0,
-            },
-            Vertex {
-                position: self.vertices[2],
-                color: self.color,
-                texture_coords: self.texture_coords[2],
-                tex_index: 0,
-            },
-            Vertex {
-                position: self.vertices[3],
-                color: self.color,
-                texture_coords: self.texture_coords[3],
-                tex_index: 0,
-            },
-        ]
+        let mut flags = 0;
+        if self.lit {
+            flags |= VERTEX_FLAG_LIT;
+        }
+        if self.wrap {
+            flags |= VERTEX_FLAG_WRAP;
+        }
+        if self.sdf.is_some() {
+            flags |= VERTEX_FLAG_SDF;
+        }
+        let vertex = |i: usize| Vertex {
+            position: self.vertices[i],
+            color: self.color,
+            texture_coords: self.texture_coords[i],
+            flags,
+            sdf: self.sdf.unwrap_or_else(|| glam::vec2(0.5, 0.0)),
+            ..Default::default()
+        };
+
+        [vertex(0), vertex(1), vertex(2), vertex(3)]
     }
 }
+
+// One quad of an instanced batch, large runs of instances sharing their textures are drawn
+// with a single instanced draw call and smaller runs are expanded into vertices
+pub struct RenderInstance {
+    pub texture: Option<Rc<Texture>>,
+    pub normal_texture: Option<Rc<Texture>>,
+    // Maps the unit quad (0, 0)..(1, 1) to world space
+    pub transform: glam::Affine2,
+    pub color: glam::Vec4,
+    // Same corner order as RenderVertices, the region has to be axis aligned
+    pub texture_coords: [glam::Vec2; 4],
+    pub lit: bool,
+    pub wrap: bool,
+}
+
+impl RenderInstance {
+    pub fn raw_instance(&self) -> Instance {
+        let mut flags = 0;
+        if self.lit {
+            flags |= VERTEX_FLAG_LIT;
+        }
+        if self.wrap {
+            flags |= VERTEX_FLAG_WRAP;
+        }
+        let [x_axis, y_axis, translation] = self.transform.to_cols_array_2d();
+        Instance {
+            x_axis,
+            y_axis,
+            translation,
+            uv_offset: self.texture_coords[1].to_array(),
+            uv_scale: (self.texture_coords[3] - self.texture_coords[1]).to_array(),
+            color: self.color.to_array(),
+            flags,
+            ..Default::default()
+        }
+    }
+
+    // Used for the runs that are too short to be instanced
+    pub fn into_vertices(self) -> RenderVertices {
+        let transform = self.transform;
+        RenderVertices {
+            texture: self.texture,
+            normal_texture: self.normal_texture,
+            vertices: TOP_LEFT_QUAD.map(|corner| {
+                transform
+                    .transform_point2(corner.truncate().truncate())
+                    .extend(0.0)
+            }),
+            color: self.color,
+            texture_coords: self.texture_coords,
+            lit: self.lit,
+            wrap: self.wrap,
+            sdf: None,
+        }
+    }
+
+    // Instances can only share a draw call when they sample the same textures
+    pub(crate) fn shares_textures(&self, other: &RenderInstance) -> bool {
+        fn same(a: &Option<Rc<Texture>>, b: &Option<Rc<Texture>>) -> bool {
+            match (a, b) {
+                (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+                (None, None) => true,
+                _ => false,
+            }
+        }
+        same(&self.texture, &other.texture) && same(&self.normal_texture, &other.normal_texture)
+    }
+}
+
+pub struct RenderLight {
+    pub position: glam::Vec2,
+    pub color: glam::Vec3,
+    pub intensity: f32,
+    pub radius: f32,
+    pub height: f32,
+    pub shadow_softness: f32,
+    // Direction, inner and outer half angles of spot lights
+    pub spot: Option<(glam::Vec2, f32, f32)>,
+}
+
+pub struct RenderLighting {
+    pub ambient: glam::Vec3,
+    pub lights: Vec<RenderLight>,
+    pub occluders: Vec<Rect>,
+}
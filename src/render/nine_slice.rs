@@ -0,0 +1,221 @@
+use crate::shapes::rectangle::Rect;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SliceFill {
+    Stretch,
+    Tile,
+}
+
+// Border insets in texels of the texture or sub texture region, corners are never scaled
+#[derive(Debug, Clone, Copy)]
+pub struct NineSlice {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+    pub edges: SliceFill,
+    pub center: SliceFill,
+}
+
+impl NineSlice {
+    pub fn new(left: f32, right: f32, top: f32, bottom: f32) -> NineSlice {
+        NineSlice {
+            left,
+            right,
+            top,
+            bottom,
+            edges: SliceFill::Stretch,
+            center: SliceFill::Stretch,
+        }
+    }
+
+    pub fn uniform(inset: f32) -> NineSlice {
+        NineSlice::new(inset, inset, inset, inset)
+    }
+
+    pub fn with_fill(mut self, edges: SliceFill, center: SliceFill) -> Self {
+        self.edges = edges;
+        self.center = center;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SliceQuad {
+    // Relative to the top left corner of the sliced rect
+    pub positions: [glam::Vec2; 4],
+    pub texture_coords: [glam::Vec2; 4],
+}
+
+impl SliceQuad {
+    // Same corner order as the sprite quads: top right, top left, bottom left, bottom right
+    pub(crate) fn new(
+        (x0, x1, u0, u1): (f32, f32, f32, f32),
+        (y0, y1, v0, v1): (f32, f32, f32, f32),
+        texture_size: glam::Vec2,
+    ) -> SliceQuad {
+        let (u0, u1) = (u0 / texture_size.x, u1 / texture_size.x);
+        let (v0, v1) = (v0 / texture_size.y, v1 / texture_size.y);
+        SliceQuad {
+            positions: [
+                glam::vec2(x1, y0),
+                glam::vec2(x0, y0),
+                glam::vec2(x0, y1),
+                glam::vec2(x1, y1),
+            ],
+            texture_coords: [
+                glam::vec2(u1, v0),
+                glam::vec2(u0, v0),
+                glam::vec2(u0, v1),
+                glam::vec2(u1, v1),
+            ],
+        }
+    }
+}
+
+// Splits the destination range into (from, to, source from, source to) segments,
+// tiles start phase texels into the source range
+pub(crate) fn slice_axis(
+    from: f32,
+    to: f32,
+    source_from: f32,
+    source_to: f32,
+    fill: SliceFill,
+    phase: f32,
+) -> Vec<(f32, f32, f32, f32)> {
+    let step = source_to - source_from;
+    if fill == SliceFill::Stretch || step <= 0.0 {
+        return vec![(from, to, source_from, source_to)];
+    }
+
+    let mut segments = Vec::new();
+    let mut position = from;
+    let mut phase = phase.rem_euclid(step);
+    while position < to {
+        let length = (step - phase).min(to - position);
+        segments.push((
+            position,
+            position + length,
+            source_from + phase,
+            source_from + phase + length,
+        ));
+        position += length;
+        phase = 0.0;
+    }
+    segments
+}
+
+pub(crate) fn nine_slice_quads(
+    nine_slice: &NineSlice,
+    size: glam::Vec2,
+    region: Rect,
+    texture_size: glam::Vec2,
+) -> Vec<SliceQuad> {
+    // Borders shrink proportionally when the rect is smaller than both insets
+    let scale_x = (size.x / (nine_slice.left + nine_slice.right)).min(1.0);
+    let scale_y = (size.y / (nine_slice.top + nine_slice.bottom)).min(1.0);
+
+    let columns = [
+        (
+            0.0,
+            nine_slice.left * scale_x,
+            region.x,
+            region.x + nine_slice.left,
+        ),
+        (
+            nine_slice.left * scale_x,
+            size.x - nine_slice.right * scale_x,
+            region.x + nine_slice.left,
+            region.right() - nine_slice.right,
+        ),
+        (
+            size.x - nine_slice.right * scale_x,
+            size.x,
+            region.right() - nine_slice.right,
+            region.right(),
+        ),
+    ];
+    let rows = [
+        (
+            0.0,
+            nine_slice.top * scale_y,
+            region.y,
+            region.y + nine_slice.top,
+        ),
+        (
+            nine_slice.top * scale_y,
+            size.y - nine_slice.bottom * scale_y,
+            region.y + nine_slice.top,
+            region.bottom() - nine_slice.bottom,
+        ),
+        (
+            size.y - nine_slice.bottom * scale_y,
+            size.y,
+            region.bottom() - nine_slice.bottom,
+            region.bottom(),
+        ),
+    ];
+
+    let mut quads = Vec::new();
+    for (row_index, row) in rows.iter().enumerate() {
+        for (column_index, column) in columns.iter().enumerate() {
+            if column.1 <= column.0 || row.1 <= row.0 {
+                continue;
+            }
+            let fill = if row_index == 1 && column_index == 1 {
+                nine_slice.center
+            } else {
+                nine_slice.edges
+            };
+            let fill_x = if column_index == 1 {
+                fill
+            } else {
+                SliceFill::Stretch
+            };
+            let fill_y = if row_index == 1 {
+                fill
+            } else {
+                SliceFill::Stretch
+            };
+
+            for y in slice_axis(row.0, row.1, row.2, row.3, fill_y, 0.0) {
+                for x in slice_axis(column.0, column.1, column.2, column.3, fill_x, 0.0) {
+                    quads.push(SliceQuad::new(x, y, texture_size));
+                }
+            }
+        }
+    }
+    quads
+}
+
+// Repeats the region across the size, scrolled by offset texels
+pub(crate) fn tiled_quads(
+    size: glam::Vec2,
+    region: Rect,
+    texture_size: glam::Vec2,
+    offset: glam::Vec2,
+) -> Vec<SliceQuad> {
+    let mut quads = Vec::new();
+    let rows = slice_axis(
+        0.0,
+        size.y,
+        region.y,
+        region.bottom(),
+        SliceFill::Tile,
+        offset.y,
+    );
+    let columns = slice_axis(
+        0.0,
+        size.x,
+        region.x,
+        region.right(),
+        SliceFill::Tile,
+        offset.x,
+    );
+    for y in &rows {
+        for x in &columns {
+            quads.push(SliceQuad::new(*x, *y, texture_size));
+        }
+    }
+    quads
+}
@@ -1,5 +1,8 @@
-use super::vertex::{QuadOrigin, Vertex, CENTER_QUAD, TOP_LEFT_QUAD};
-use super::{RenderQuad, RenderTexture, RenderVertices};
+use super::vertex::{
+    Instance, QuadOrigin, Vertex, CENTER_QUAD, NO_NORMAL_MAP, TOP_LEFT_QUAD,
+    VERTEX_FLAG_SINGLE_CHANNEL,
+};
+use super::{RenderInstance, RenderTexture, RenderVertices};
 use crate::render::texture::Texture;
 use crate::shapes::rectangle::Rect;
 use std::rc::Rc;
@@ -8,27 +11,61 @@ pub const MAX_QUAD_COUNT: usize = 100000;
 pub const MAX_VERTEX_COUNT: usize = MAX_QUAD_COUNT * 4;
 pub const MAX_INDEX_COUNT: usize = MAX_QUAD_COUNT * 6;
 pub const MAX_TEXTURE_COUNT: usize = 32;
+// Shorter runs of instances sharing their textures are expanded into vertices
+pub const INSTANCING_THRESHOLD: usize = 64;
 
 pub enum RenderItem {
-    RenderQuad(RenderQuad),
     RenderTexture(RenderTexture),
     RenderVertices(RenderVertices),
+    RenderInstance(RenderInstance),
 }
 
 pub struct TextureBind {
     pub texture_bind_group: wgpu::BindGroup,
+    // Vertices, or instances when instanced
     pub from: u64,
     pub to: u64,
+    pub instanced: bool,
+}
+
+#[derive(Default)]
+pub struct RenderView {
+    pub camera: Option<glam::Mat4>,
+    pub viewport: Option<Rect>,
+}
+
+pub struct ViewStep {
+    pub view: RenderView,
+    pub texture_binds: std::ops::Range<usize>,
 }
 
 pub struct RenderStep {
     pub buffer_vertices: Vec<Vertex>,
+    pub buffer_instances: Vec<Instance>,
     pub texture_binds: Vec<TextureBind>,
+    pub views: Vec<ViewStep>,
+}
+
+fn texture_slot(textures: &mut Vec<Rc<Texture>>, texture: Rc<Texture>) -> u32 {
+    match textures.iter().position(|t| Rc::ptr_eq(t, &texture)) {
+        Some(i) => i as u32,
+        None => {
+            textures.push(texture);
+            textures.len() as u32 - 1
+        }
+    }
+}
+
+// Textures bound to the vertices that are not drawn yet, starting at vertex from
+struct PendingBatch {
+    textures: Vec<Rc<Texture>>,
+    from: u64,
 }
 
 pub struct Render2dData {
     white_texture: Rc<Texture>,
     render_items: Vec<RenderItem>,
+    views: Vec<(RenderView, usize)>,
     texture_max: usize,
 }
 
@@ -47,6 +84,7 @@ impl Render2dData {
             white_texture,
             texture_max,
             render_items: Vec::new(),
+            views: Vec::new(),
         }
     }
 
@@ -56,6 +94,11 @@ impl Render2dData {
 
     pub fn begin_batch(&mut self) {
         self.render_items.clear();
+        self.views.clear();
+    }
+
+    pub fn begin_view(&mut self, view: RenderView) {
+        self.views.push((view, self.render_items.len()));
     }
 
     pub fn get_render_vertices_and_textures(
@@ -65,118 +108,299 @@ impl Render2dData {
     ) -> RenderStep {
         let mut render_steps = RenderStep {
             buffer_vertices: Vec::new(),
+            buffer_instances: Vec::new(),
             texture_binds: Vec::new(),
+            views: Vec::new(),
         };
 
-        let mut textures = Vec::new();
-        let mut from = 0;
-
-        for render_item in self.render_items.drain(..) {
-            let (mut vertices, texture) = match render_item {
-                RenderItem::RenderQuad(_render_quad) => {
-                    todo!();
-                }
-                RenderItem::RenderTexture(_render_texture) => {
-                    todo!();
-                }
-                RenderItem::RenderVertices(render_vertices) => (
-                    render_vertices.raw_vertices(),
-                    render_vertices
-                        .texture
-                        .unwrap_or(self.white_texture.clone()),
-                ),
-            };
-
-            let mut tex_index = None;
-            for (i, t) in textures.iter().enumerate() {
-                if Rc::ptr_eq(t, &texture) {
-                    tex_index = Some(i as u32);
-                    break;
-                }
-            }
-
-            if tex_index.is_none() {
-                if textures.len() < self.texture_max {
-                    textures.push(texture);
-                    tex_index = Some(textures.len() as u32 - 1);
-                } else {
-                    let mut textures_bind_group_entries = Vec::new();
-
-                    for (i, texture) in textures.iter().enumerate() {
-                        if i == 0 {
-                            textures_bind_group_entries.push(wgpu::BindGroupEntry {
-                                binding: 0,
-                                resource: wgpu::BindingResource::Sampler(&texture.sampler),
-                            });
+        let render_items = std::mem::take(&mut self.render_items);
+        let mut views = std::mem::take(&mut self.views);
+        // Items added before the first view use the default camera
+        if views
+            .first()
+            .map_or(true, |(_, first_item)| *first_item > 0)
+        {
+            views.insert(0, (RenderView::default(), 0));
+        }
+        let last_items = views
+            .iter()
+            .skip(1)
+            .map(|(_, first_item)| *first_item)
+            .chain(std::iter::once(render_items.len()))
+            .collect::<Vec<_>>();
+
+        let mut render_items = render_items.into_iter();
+        let mut batch = PendingBatch {
+            textures: Vec::new(),
+            from: 0,
+        };
+        // Items past the size of the vertex and instance buffers are dropped
+        let mut dropped = 0;
+
+        for ((view, first_item), last_item) in views.into_iter().zip(last_items) {
+            let texture_binds_from = render_steps.texture_binds.len();
+            let mut view_items = render_items
+                .by_ref()
+                .take(last_item - first_item)
+                .collect::<Vec<_>>()
+                .into_iter()
+                .peekable();
+
+            while let Some(render_item) = view_items.next() {
+                match render_item {
+                    RenderItem::RenderTexture(_render_texture) => {
+                        todo!();
+                    }
+                    RenderItem::RenderVertices(_)
+                        if render_steps.buffer_vertices.len() + 4 > MAX_VERTEX_COUNT =>
+                    {
+                        dropped += 1;
+                    }
+                    RenderItem::RenderVertices(render_vertices) => {
+                        self.push_vertices(
+                            device,
+                            texture_bind_group_layout,
+                            &mut batch,
+                            &mut render_steps,
+                            render_vertices,
+                        );
+                    }
+                    RenderItem::RenderInstance(render_instance) => {
+                        // Consecutive instances sharing their textures form a run
+                        let mut run = vec![render_instance];
+                        while let Some(RenderItem::RenderInstance(next)) =
+                            view_items.next_if(|item| match item {
+                                RenderItem::RenderInstance(next) => next.shares_textures(&run[0]),
+                                _ => false,
+                            })
+                        {
+                            run.push(next);
                         }
 
-                        textures_bind_group_entries.push(wgpu::BindGroupEntry {
-                            binding: i as u32 + 1,
-                            resource: wgpu::BindingResource::TextureView(&texture.view),
-                        })
+                        if run.len() >= INSTANCING_THRESHOLD {
+                            let free = MAX_QUAD_COUNT - render_steps.buffer_instances.len();
+                            if run.len() > free {
+                                dropped += run.len() - free;
+                                run.truncate(free);
+                            }
+                            if run.is_empty() {
+                                continue;
+                            }
+                            self.push_instances(
+                                device,
+                                texture_bind_group_layout,
+                                &mut batch,
+                                &mut render_steps,
+                                run,
+                            );
+                        } else {
+                            for render_instance in run {
+                                if render_steps.buffer_vertices.len() + 4 > MAX_VERTEX_COUNT {
+                                    dropped += 1;
+                                    continue;
+                                }
+                                self.push_vertices(
+                                    device,
+                                    texture_bind_group_layout,
+                                    &mut batch,
+                                    &mut render_steps,
+                                    render_instance.into_vertices(),
+                                );
+                            }
+                        }
                     }
-                    let texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                        layout: &texture_bind_group_layout,
-                        entries: &textures_bind_group_entries,
-                        label: Some("texture_bind_group"),
-                    });
-
-                    render_steps.texture_binds.push(TextureBind {
-                        texture_bind_group,
-                        from,
-                        to: render_steps.buffer_vertices.len() as u64,
-                    });
-
-                    from = render_steps.buffer_vertices.len() as u64;
-                    textures = Vec::new();
-                    textures.push(texture);
-                    tex_index = Some(textures.len() as u32 - 1);
                 }
             }
 
-            if let Some(tex_index) = tex_index {
-                for v in &mut vertices {
-                    v.tex_index = tex_index;
-                }
+            // Every view needs its own draw calls, so the textures are bound again
+            if render_steps.buffer_vertices.len() as u64 > batch.from {
+                self.push_texture_bind(
+                    device,
+                    texture_bind_group_layout,
+                    &mut batch,
+                    &mut render_steps,
+                );
             }
 
-            render_steps.buffer_vertices.extend_from_slice(&vertices);
+            render_steps.views.push(ViewStep {
+                view,
+                texture_binds: texture_binds_from..render_steps.texture_binds.len(),
+            });
         }
 
-        if render_steps.buffer_vertices.len() as u64 > from {
-            for _ in textures.len()..self.texture_max {
-                textures.push(self.white_texture.clone());
-            }
+        if dropped > 0 {
+            log::warn!(
+                "The render buffers are full, {} quads were not drawn this frame",
+                dropped
+            );
+        }
 
-            let mut textures_bind_group_entries = Vec::new();
+        render_steps
+    }
 
-            for (i, texture) in textures.iter().enumerate() {
-                if i == 0 {
-                    textures_bind_group_entries.push(wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::Sampler(&texture.sampler),
-                    });
-                }
+    // Binds the texture and its normal map to the pending batch, the batch is drawn first
+    // when there are not enough free texture slots. Returns the tex_index, normal_index
+    // and the flags of the texture.
+    fn bind_textures(
+        &self,
+        device: &wgpu::Device,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        batch: &mut PendingBatch,
+        render_steps: &mut RenderStep,
+        texture: Option<Rc<Texture>>,
+        normal_texture: Option<Rc<Texture>>,
+    ) -> (u32, u32, u32) {
+        let texture = texture.unwrap_or(self.white_texture.clone());
+
+        // The normal map has to be bound together with its texture
+        let missing = std::iter::once(&texture)
+            .chain(normal_texture.iter())
+            .filter(|t| !batch.textures.iter().any(|bound| Rc::ptr_eq(bound, *t)))
+            .count();
+        if batch.textures.len() + missing > self.texture_max {
+            self.push_texture_bind(device, texture_bind_group_layout, batch, render_steps);
+        }
 
-                textures_bind_group_entries.push(wgpu::BindGroupEntry {
-                    binding: i as u32 + 1,
-                    resource: wgpu::BindingResource::TextureView(&texture.view),
-                })
-            }
-            let texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                layout: &texture_bind_group_layout,
-                entries: &textures_bind_group_entries,
-                label: Some("texture_bind_group"),
-            });
+        let flags = if texture.single_channel {
+            VERTEX_FLAG_SINGLE_CHANNEL
+        } else {
+            0
+        };
+        let tex_index = texture_slot(&mut batch.textures, texture);
+        let normal_index = normal_texture
+            .map(|normal_texture| texture_slot(&mut batch.textures, normal_texture))
+            .unwrap_or(NO_NORMAL_MAP);
 
-            render_steps.texture_binds.push(TextureBind {
-                texture_bind_group,
-                from,
-                to: render_steps.buffer_vertices.len() as u64,
-            });
+        (tex_index, normal_index, flags)
+    }
+
+    fn push_vertices(
+        &self,
+        device: &wgpu::Device,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        batch: &mut PendingBatch,
+        render_steps: &mut RenderStep,
+        render_vertices: RenderVertices,
+    ) {
+        let mut vertices = render_vertices.raw_vertices();
+        let (tex_index, normal_index, flags) = self.bind_textures(
+            device,
+            texture_bind_group_layout,
+            batch,
+            render_steps,
+            render_vertices.texture,
+            render_vertices.normal_texture,
+        );
+
+        for v in &mut vertices {
+            v.tex_index = tex_index;
+            v.normal_index = normal_index;
+            v.flags |= flags;
+        }
+
+        render_steps.buffer_vertices.extend_from_slice(&vertices);
+    }
+
+    // The whole run is a single instanced draw call, the pending vertices are drawn
+    // before it to keep the draw order
+    fn push_instances(
+        &self,
+        device: &wgpu::Device,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        batch: &mut PendingBatch,
+        render_steps: &mut RenderStep,
+        run: Vec<RenderInstance>,
+    ) {
+        if render_steps.buffer_vertices.len() as u64 > batch.from {
+            self.push_texture_bind(device, texture_bind_group_layout, batch, render_steps);
         }
 
+        let (tex_index, normal_index, flags) = self.bind_textures(
+            device,
+            texture_bind_group_layout,
+            batch,
+            render_steps,
+            run[0].texture.clone(),
+            run[0].normal_texture.clone(),
+        );
+
+        let instances_from = render_steps.buffer_instances.len() as u64;
         render_steps
+            .buffer_instances
+            .extend(run.iter().map(|render_instance| {
+                let mut instance = render_instance.raw_instance();
+                instance.tex_index = tex_index;
+                instance.normal_index = normal_index;
+                instance.flags |= flags;
+                instance
+            }));
+
+        let texture_bind_group =
+            self.create_texture_bind_group(device, texture_bind_group_layout, batch);
+        render_steps.texture_binds.push(TextureBind {
+            texture_bind_group,
+            from: instances_from,
+            to: render_steps.buffer_instances.len() as u64,
+            instanced: true,
+        });
+    }
+
+    fn push_texture_bind(
+        &self,
+        device: &wgpu::Device,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        batch: &mut PendingBatch,
+        render_steps: &mut RenderStep,
+    ) {
+        let texture_bind_group =
+            self.create_texture_bind_group(device, texture_bind_group_layout, batch);
+
+        let to = render_steps.buffer_vertices.len() as u64;
+        render_steps.texture_binds.push(TextureBind {
+            texture_bind_group,
+            from: batch.from,
+            to,
+            instanced: false,
+        });
+
+        batch.from = to;
+    }
+
+    // Unused slots get the white texture, the batch textures are cleared afterwards
+    fn create_texture_bind_group(
+        &self,
+        device: &wgpu::Device,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        batch: &mut PendingBatch,
+    ) -> wgpu::BindGroup {
+        let textures = &mut batch.textures;
+        for _ in textures.len()..self.texture_max {
+            textures.push(self.white_texture.clone());
+        }
+
+        let mut textures_bind_group_entries = Vec::new();
+        for (i, texture) in textures.iter().enumerate() {
+            textures_bind_group_entries.push(wgpu::BindGroupEntry {
+                binding: i as u32 + 1,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            });
+            textures_bind_group_entries.push(wgpu::BindGroupEntry {
+                binding: (self.texture_max + i) as u32 + 1,
+                resource: wgpu::BindingResource::Sampler(&texture.sampler),
+            });
+            textures_bind_group_entries.push(wgpu::BindGroupEntry {
+                binding: (self.texture_max * 2 + i) as u32 + 1,
+                resource: wgpu::BindingResource::Sampler(&texture.wrap_sampler),
+            });
+        }
+        let texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &texture_bind_group_layout,
+            entries: &textures_bind_group_entries,
+            label: Some("texture_bind_group"),
+        });
+
+        textures.clear();
+        texture_bind_group
     }
 
     // pub fn add_vertices(
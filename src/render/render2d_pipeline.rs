@@ -3,14 +3,22 @@ use std::rc::Rc;
 use log::warn;
 use wgpu::util::DeviceExt;
 
+use crate::shapes::rectangle::Rect;
+
 use super::{
-    render2d_data::{Render2dData, RenderItem, MAX_INDEX_COUNT, MAX_QUAD_COUNT},
+    render2d_data::{
+        Render2dData, RenderItem, RenderView, MAX_INDEX_COUNT, MAX_QUAD_COUNT, MAX_VERTEX_COUNT,
+    },
     renderer::RenderContext,
     texture::Texture,
-    vertex::{QuadOrigin, Vertex},
-    RenderQuad, RenderTexture, RenderVertices,
+    vertex::{Instance, QuadOrigin, Vertex},
+    RenderInstance, RenderLighting, RenderQuad, RenderTexture, RenderVertices,
 };
 
+pub const MAX_VIEW_COUNT: usize = 16;
+pub const MAX_LIGHT_COUNT: usize = 32;
+pub const MAX_OCCLUDER_COUNT: usize = 64;
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Uniforms {
@@ -25,21 +33,104 @@ impl Uniforms {
     }
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightUniform {
+    pub position: [f32; 4],
+    pub color: [f32; 4],
+    pub direction: [f32; 4],
+    pub params: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightingUniforms {
+    pub ambient: [f32; 4],
+    pub counts: [u32; 4],
+    pub lights: [LightUniform; MAX_LIGHT_COUNT],
+    pub occluders: [[f32; 4]; MAX_OCCLUDER_COUNT],
+}
+
+impl LightingUniforms {
+    pub fn new(lighting: Option<&RenderLighting>) -> Self {
+        let mut uniforms: LightingUniforms = bytemuck::Zeroable::zeroed();
+        let lighting = match lighting {
+            Some(lighting) => lighting,
+            None => return uniforms,
+        };
+
+        if lighting.lights.len() > MAX_LIGHT_COUNT {
+            warn!(
+                "Only {} lights can be drawn, {} were requested",
+                MAX_LIGHT_COUNT,
+                lighting.lights.len()
+            );
+        }
+        if lighting.occluders.len() > MAX_OCCLUDER_COUNT {
+            warn!(
+                "Only {} light occluders can be drawn, {} were requested",
+                MAX_OCCLUDER_COUNT,
+                lighting.occluders.len()
+            );
+        }
+
+        uniforms.ambient = lighting.ambient.extend(1.0).to_array();
+
+        for (uniform, light) in uniforms.lights.iter_mut().zip(&lighting.lights) {
+            let (direction, inner, outer, is_spot) = match light.spot {
+                Some((direction, inner, outer)) => (direction, inner.cos(), outer.cos(), 1.0),
+                None => (glam::Vec2::ZERO, 0.0, 0.0, 0.0),
+            };
+            *uniform = LightUniform {
+                position: [
+                    light.position.x,
+                    light.position.y,
+                    light.radius,
+                    light.intensity,
+                ],
+                color: light.color.extend(light.height).to_array(),
+                direction: [direction.x, direction.y, inner, outer],
+                params: [is_spot, light.shadow_softness, 0.0, 0.0],
+            };
+        }
+
+        for (uniform, occluder) in uniforms.occluders.iter_mut().zip(&lighting.occluders) {
+            *uniform = [occluder.x, occluder.y, occluder.width, occluder.height];
+        }
+
+        uniforms.counts = [
+            lighting.lights.len().min(MAX_LIGHT_COUNT) as u32,
+            lighting.occluders.len().min(MAX_OCCLUDER_COUNT) as u32,
+            0,
+            0,
+        ];
+
+        uniforms
+    }
+}
+
 pub struct Render2DPineline {
     device: Rc<wgpu::Device>,
     queue: Rc<wgpu::Queue>,
 
     render_pipeline: wgpu::RenderPipeline,
+    instanced_pipeline: wgpu::RenderPipeline,
     vertex_buffer: wgpu::Buffer,
+    instance_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
 
     render_data: Render2dData,
 
     default_camera: glam::Mat4,
     camera_buffer: wgpu::Buffer,
+    camera_uniform_alignment: u64,
 
     camera_bind_group: wgpu::BindGroup,
     texture_bind_group_layout: wgpu::BindGroupLayout,
+
+    lighting: LightingUniforms,
+    lighting_buffer: wgpu::Buffer,
+    lighting_bind_group: wgpu::BindGroup,
 }
 
 impl Render2DPineline {
@@ -53,6 +144,7 @@ impl Render2DPineline {
     ) -> Render2DPineline {
         let vs_src = include_str!("../../shaders/render2d_shader.vert");
         let fs_src = include_str!("../../shaders/render2d_shader.frag");
+        let instanced_vs_src = include_str!("../../shaders/render2d_instanced.vert");
         // let vs_src = std::fs::read_to_string("shaders/render2d_shader.vert").unwrap();
         // let fs_src = std::fs::read_to_string("shaders/render2d_shader.frag").unwrap();
         let mut compiler = shaderc::Compiler::new().unwrap();
@@ -74,8 +166,18 @@ impl Render2DPineline {
                 None,
             )
             .unwrap();
+        let instanced_vs_spirv = compiler
+            .compile_into_spirv(
+                &instanced_vs_src,
+                shaderc::ShaderKind::Vertex,
+                "instanced.vert",
+                "main",
+                None,
+            )
+            .unwrap();
         let vs_data = wgpu::util::make_spirv(vs_spirv.as_binary_u8());
         let fs_data = wgpu::util::make_spirv(fs_spirv.as_binary_u8());
+        let instanced_vs_data = wgpu::util::make_spirv(instanced_vs_spirv.as_binary_u8());
 
         let vs_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
             label: Some("Vertex Shader"),
@@ -85,14 +187,14 @@ impl Render2DPineline {
             label: Some("Fragment Shader"),
             source: fs_data,
         });
+        let instanced_vs_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("Instanced Vertex Shader"),
+            source: instanced_vs_data,
+        });
 
+        // Slot i uses binding i + 1 for the texture, max_textures + i + 1 for its sampler
+        // and max_textures * 2 + i + 1 for its repeating sampler
         let mut texture_bind_group_layout_entries = Vec::new();
-        texture_bind_group_layout_entries.push(wgpu::BindGroupLayoutEntry {
-            binding: 0,
-            visibility: wgpu::ShaderStages::FRAGMENT,
-            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-            count: None,
-        });
         for i in 0..max_textures {
             texture_bind_group_layout_entries.push(wgpu::BindGroupLayoutEntry {
                 binding: i as u32 + 1,
@@ -105,6 +207,14 @@ impl Render2DPineline {
                 count: None,
             })
         }
+        for i in 0..max_textures * 2 {
+            texture_bind_group_layout_entries.push(wgpu::BindGroupLayoutEntry {
+                binding: (max_textures + i) as u32 + 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            })
+        }
 
         let texture_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -119,6 +229,23 @@ impl Render2DPineline {
                 entries: &[wgpu::BindGroupLayoutEntry {
                     binding: 0,
                     visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<Uniforms>() as u64
+                        ),
+                    },
+                    count: None,
+                }],
+                label: Some("camera_bind_group_layout"),
+            });
+
+        let lighting_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -126,13 +253,17 @@ impl Render2DPineline {
                     },
                     count: None,
                 }],
-                label: Some("camera_bind_group_layout"),
+                label: Some("lighting_bind_group_layout"),
             });
 
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&texture_bind_group_layout, &camera_bind_group_layout],
+                bind_group_layouts: &[
+                    &texture_bind_group_layout,
+                    &camera_bind_group_layout,
+                    &lighting_bind_group_layout,
+                ],
                 push_constant_ranges: &[],
             });
 
@@ -163,13 +294,48 @@ impl Render2DPineline {
             multiview: None,
         });
 
+        // Same layout and fragment shader, the quad corners come from the vertex index
+        let instanced_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Instanced Render Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &instanced_vs_module,
+                entry_point: "main",
+                buffers: &[Instance::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &fs_module,
+                entry_point: "main",
+                targets: &[wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                }],
+            }),
+            primitive: wgpu::PrimitiveState {
+                front_face: wgpu::FrontFace::Ccw,
+                ..Default::default()
+            },
+
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
         let render_data = Render2dData::new(max_textures as usize, white_texture);
 
-        let vertices: [Vertex; MAX_QUAD_COUNT] = [Vertex::default(); MAX_QUAD_COUNT];
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(&vertices),
+            size: (std::mem::size_of::<Vertex>() * MAX_VERTEX_COUNT) as u64,
             usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Instance Buffer"),
+            size: (std::mem::size_of::<Instance>() * MAX_QUAD_COUNT) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
         });
 
         // let mut indices: [u16; MAX_INDEX_COUNT] = [0u16; MAX_INDEX_COUNT];
@@ -203,68 +369,88 @@ impl Render2DPineline {
         let default_camera =
             glam::Mat4::orthographic_rh_gl(0.0, width as f32, height as f32, 0.0, -1.0, 1.0);
 
-        let camera_uniform = Uniforms::new(&default_camera);
+        // Every view gets its own slot in the camera buffer, selected with a dynamic offset
+        let camera_uniform_size = std::mem::size_of::<Uniforms>() as u64;
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        let camera_uniform_alignment =
+            ((camera_uniform_size + alignment - 1) / alignment) * alignment;
 
-        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        let camera_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Camera Buffer"),
-            contents: bytemuck::cast_slice(&[camera_uniform]),
+            size: camera_uniform_alignment * MAX_VIEW_COUNT as u64,
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
         });
 
         let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &camera_bind_group_layout,
             entries: &[wgpu::BindGroupEntry {
                 binding: 0,
-                resource: camera_buffer.as_entire_binding(),
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &camera_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(camera_uniform_size),
+                }),
             }],
             label: Some("camera_bind_group"),
         });
 
+        let lighting = LightingUniforms::new(None);
+        let lighting_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Lighting Buffer"),
+            contents: bytemuck::cast_slice(&[lighting]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let lighting_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &lighting_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: lighting_buffer.as_entire_binding(),
+            }],
+            label: Some("lighting_bind_group"),
+        });
+
         Render2DPineline {
             device: device.clone(),
             queue: queue.clone(),
 
             render_pipeline,
+            instanced_pipeline,
             vertex_buffer,
+            instance_buffer,
             index_buffer,
 
             render_data,
 
             default_camera,
             camera_buffer,
+            camera_uniform_alignment,
 
             camera_bind_group,
             texture_bind_group_layout,
+
+            lighting,
+            lighting_buffer,
+            lighting_bind_group,
         }
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
         self.default_camera =
             glam::Mat4::orthographic_rh_gl(0.0, width as f32, height as f32, 0.0, -1.0, 1.0);
-
-        self.default_camera();
     }
 
-    pub fn set_camera(&self, camera: glam::Mat4) {
-        let camera_uniform = Uniforms::new(&camera);
-        self.queue.write_buffer(
-            &self.camera_buffer,
-            0,
-            bytemuck::cast_slice(&[camera_uniform]),
-        );
+    pub fn begin_batch(&mut self) {
+        self.render_data.begin_batch();
     }
 
-    pub fn default_camera(&self) {
-        let camera_uniform = Uniforms::new(&self.default_camera);
-        self.queue.write_buffer(
-            &self.camera_buffer,
-            0,
-            bytemuck::cast_slice(&[camera_uniform]),
-        );
+    pub fn begin_view(&mut self, view: RenderView) {
+        self.render_data.begin_view(view);
     }
 
-    pub fn begin_batch(&mut self) {
-        self.render_data.begin_batch();
+    pub fn set_lighting(&mut self, lighting: Option<&RenderLighting>) {
+        self.lighting = LightingUniforms::new(lighting);
     }
 
     pub fn draw<'a>(&'a mut self, render_context: &mut RenderContext) {
@@ -277,6 +463,43 @@ impl Render2DPineline {
             0,
             bytemuck::cast_slice(&render_steps.buffer_vertices),
         );
+        if !render_steps.buffer_instances.is_empty() {
+            self.queue.write_buffer(
+                &self.instance_buffer,
+                0,
+                bytemuck::cast_slice(&render_steps.buffer_instances),
+            );
+        }
+
+        if render_steps.views.len() > MAX_VIEW_COUNT {
+            warn!(
+                "Only {} views can be drawn, {} were requested",
+                MAX_VIEW_COUNT,
+                render_steps.views.len()
+            );
+        }
+        let views = &render_steps.views[..render_steps.views.len().min(MAX_VIEW_COUNT)];
+
+        for (i, view_step) in views.iter().enumerate() {
+            let camera_uniform = Uniforms::new(
+                view_step
+                    .view
+                    .camera
+                    .as_ref()
+                    .unwrap_or(&self.default_camera),
+            );
+            self.queue.write_buffer(
+                &self.camera_buffer,
+                i as u64 * self.camera_uniform_alignment,
+                bytemuck::cast_slice(&[camera_uniform]),
+            );
+        }
+
+        self.queue.write_buffer(
+            &self.lighting_buffer,
+            0,
+            bytemuck::cast_slice(&[self.lighting]),
+        );
 
         {
             let mut render_pass =
@@ -302,18 +525,60 @@ impl Render2DPineline {
 
             render_pass.set_pipeline(&self.render_pipeline);
             render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-
-            for texture_bind in &render_steps.texture_binds {
-                let indices_from = (texture_bind.from / 4) * 6;
-                let indices_to = (texture_bind.to / 4) * 6;
-
-                render_pass.set_bind_group(0, &texture_bind.texture_bind_group, &[]);
-                render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
-
-                render_pass
-                    .set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-                // TOOD remove cast later
-                render_pass.draw_indexed(indices_from as u32..indices_to as u32, 0, 0..1);
+            let mut instanced = false;
+            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.set_bind_group(2, &self.lighting_bind_group, &[]);
+
+            let target_size = render_context.size.as_vec2();
+            for (i, view_step) in views.iter().enumerate() {
+                let viewport = view_step.view.viewport.unwrap_or(Rect::new(
+                    0.0,
+                    0.0,
+                    target_size.x,
+                    target_size.y,
+                ));
+                // The viewport must stay inside the render target
+                let x = viewport.x.clamp(0.0, target_size.x);
+                let y = viewport.y.clamp(0.0, target_size.y);
+                let width = viewport.width.min(target_size.x - x);
+                let height = viewport.height.min(target_size.y - y);
+                if width <= 0.0 || height <= 0.0 {
+                    continue;
+                }
+                render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+                render_pass.set_bind_group(
+                    1,
+                    &self.camera_bind_group,
+                    &[(i as u64 * self.camera_uniform_alignment) as u32],
+                );
+
+                for texture_bind in &render_steps.texture_binds[view_step.texture_binds.clone()] {
+                    if texture_bind.instanced != instanced {
+                        instanced = texture_bind.instanced;
+                        if instanced {
+                            render_pass.set_pipeline(&self.instanced_pipeline);
+                            render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+                        } else {
+                            render_pass.set_pipeline(&self.render_pipeline);
+                            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+                        }
+                    }
+
+                    render_pass.set_bind_group(0, &texture_bind.texture_bind_group, &[]);
+                    if instanced {
+                        // The first 6 indices make the unit quad of every instance
+                        render_pass.draw_indexed(
+                            0..6,
+                            0,
+                            texture_bind.from as u32..texture_bind.to as u32,
+                        );
+                    } else {
+                        let indices_from = (texture_bind.from / 4) * 6;
+                        let indices_to = (texture_bind.to / 4) * 6;
+                        // TOOD remove cast later
+                        render_pass.draw_indexed(indices_from as u32..indices_to as u32, 0, 0..1);
+                    }
+                }
             }
         }
 
@@ -337,9 +602,14 @@ impl Render2DPineline {
             .add_render_item(RenderItem::RenderVertices(vertices));
     }
 
+    pub fn draw_instance(&mut self, instance: RenderInstance) {
+        self.render_data
+            .add_render_item(RenderItem::RenderInstance(instance));
+    }
+
     pub fn draw_quad(&mut self, quad: RenderQuad) {
         self.render_data
-            .add_render_item(RenderItem::RenderQuad(quad));
+            .add_render_item(RenderItem::RenderVertices(quad.into_vertices()));
     }
 
     pub fn draw_texture(&mut self, texture: RenderTexture) {
@@ -1,14 +1,28 @@
 use super::{
-    render2d_pipeline::Render2DPineline, RenderQuad, RenderText, RenderTexture, RenderVertices,
+    render2d_data::RenderView, render2d_pipeline::Render2DPineline, RenderGlyphs, RenderInstance,
+    RenderLighting, RenderQuad, RenderRichText, RenderText, RenderTexture, RenderVertices,
+};
+use crate::{
+    error::{BreakoutError, BreakoutResult},
+    font::{
+        layout::PositionedGlyph,
+        markup::{RichContent, RichImage, RichSpan},
+        sdf::{TextEffects, TextPass},
+    },
+    shapes::rectangle::Rect,
+};
+use log::{error, info, warn};
+use std::{
+    rc::Rc,
+    sync::{Arc, Mutex},
 };
-use log::info;
-use std::rc::Rc;
 use winit::window::Window;
 
 pub struct RenderContext {
     pub output: wgpu::SurfaceTexture,
     pub view: wgpu::TextureView,
     pub encoder: wgpu::CommandEncoder,
+    pub size: glam::UVec2,
 }
 
 pub struct Renderer {
@@ -20,6 +34,8 @@ pub struct Renderer {
     render2d_pipeline: Render2DPineline,
     clear_color: wgpu::Color,
     display_size: Option<glam::UVec2>,
+    minimized: bool,
+    device_error: Arc<Mutex<Option<wgpu::Error>>>,
 }
 
 impl Renderer {
@@ -65,6 +81,18 @@ impl Renderer {
             .unwrap();
         info!("Limits {:#?}", device.limits());
 
+        // wgpu panics on uncaptured errors by default, keep them until the next frame instead
+        let device_error = Arc::new(Mutex::new(None));
+        {
+            let device_error = device_error.clone();
+            device.on_uncaptured_error(move |e| {
+                error!("Device error: {}", e);
+                if let Ok(mut device_error) = device_error.lock() {
+                    device_error.get_or_insert(e);
+                }
+            });
+        }
+
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface.get_preferred_format(&adapter).unwrap(),
@@ -79,7 +107,7 @@ impl Renderer {
         let queue = Rc::new(queue);
 
         //TODO max_texture_xyz
-        let render2d_pipeline = Render2DPineline::new(size.x, size.y, 2, &device, &queue, &config);
+        let render2d_pipeline = Render2DPineline::new(size.x, size.y, 4, &device, &queue, &config);
 
         let clear_color = wgpu::Color {
             r: 0.1,
@@ -97,6 +125,8 @@ impl Renderer {
             render2d_pipeline,
             clear_color,
             display_size: None,
+            minimized: size.x == 0 || size.y == 0,
+            device_error,
         }
     }
 
@@ -118,18 +148,23 @@ impl Renderer {
 
     pub fn set_display_size(&mut self, display_size: glam::UVec2) {
         self.display_size = Some(display_size);
+        self.render2d_pipeline
+            .resize(display_size.x, display_size.y);
+    }
+
+    pub fn is_minimized(&self) -> bool {
+        self.minimized
     }
 
     pub fn reconfigure(&mut self) {
-        if let Some(display_size) = self.display_size {
-            self.resize(display_size);
-        } else {
-            self.resize(self.size);
+        if !self.minimized {
+            self.surface.configure(&self.device, &self.config);
         }
     }
 
     pub fn resize(&mut self, new_size: glam::UVec2) {
-        if new_size.x > 0 && new_size.y > 0 {
+        self.minimized = new_size.x == 0 || new_size.y == 0;
+        if !self.minimized {
             self.size = new_size;
             self.config.width = new_size.x;
             self.config.height = new_size.y;
@@ -149,17 +184,40 @@ impl Renderer {
         }
     }
 
-    pub fn begin_draw(&mut self, camera: Option<glam::Mat4>) {
-        if let Some(camera) = camera {
-            self.render2d_pipeline.set_camera(camera);
-        } else {
-            self.render2d_pipeline.default_camera();
-        }
+    pub fn begin_draw(&mut self) {
         self.render2d_pipeline.begin_batch();
     }
 
-    pub fn end_draw(&mut self) {
-        let output = self.surface.get_current_texture().unwrap();
+    // Everything drawn after this call uses the camera and viewport, until the next view
+    pub fn begin_view(&mut self, camera: Option<glam::Mat4>, viewport: Option<Rect>) {
+        self.render2d_pipeline
+            .begin_view(RenderView { camera, viewport });
+    }
+
+    pub fn end_draw(&mut self) -> BreakoutResult {
+        if let Some(e) = self.device_error.lock().ok().and_then(|mut e| e.take()) {
+            return Err(BreakoutError::DeviceError(e));
+        }
+
+        if self.minimized {
+            return Ok(());
+        }
+
+        let output = match self.surface.get_current_texture() {
+            Ok(output) => output,
+            // Reconfigure the surface and skip the frame, the next one will be fine
+            Err(wgpu::SurfaceError::Lost) | Err(wgpu::SurfaceError::Outdated) => {
+                warn!("Surface lost or outdated, reconfiguring");
+                self.reconfigure();
+                return Ok(());
+            }
+            Err(wgpu::SurfaceError::Timeout) => {
+                warn!("Surface timeout, skipping frame");
+                return Ok(());
+            }
+            // The system is out of memory, let the game decide what to do
+            Err(e) => return Err(BreakoutError::SurfaceError(e)),
+        };
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
@@ -174,12 +232,20 @@ impl Renderer {
             output,
             view,
             encoder,
+            size: self.size,
         };
         self.render2d_pipeline.draw(&mut render_context);
 
         self.queue
             .submit(std::iter::once(render_context.encoder.finish()));
         render_context.output.present();
+
+        Ok(())
+    }
+
+    // Lighting is disabled when None
+    pub fn set_lighting(&mut self, lighting: Option<&RenderLighting>) {
+        self.render2d_pipeline.set_lighting(lighting);
     }
 
     pub fn draw_quad(&mut self, quad: RenderQuad) {
@@ -190,23 +256,148 @@ impl Renderer {
         self.render2d_pipeline.draw_texture(texture);
     }
 
-    pub fn draw_text(&mut self, _text: RenderText) {
-        _text.font.draw_vertices(
-            _text.text,
-            _text.position,
-            _text.size,
-            |texture, vertices, texture_coords| {
+    pub fn draw_text(&mut self, text: RenderText) {
+        let layout = text.font.layout(text.text, text.size, &text.options);
+        let transform = glam::Affine2::from_scale_angle_translation(text.scale, 0.0, text.position);
+        let glyphs = text.font.glyph_quads(&layout, transform, text.size);
+        self.draw_glyphs(RenderGlyphs {
+            glyphs: &glyphs,
+            font: &text.font,
+            size: text.size,
+            color: text.color,
+            effects: text.effects,
+        });
+    }
+
+    // Each pass of the effects moves and tints the same quads
+    pub fn draw_glyphs(&mut self, text: RenderGlyphs) {
+        let passes = TextPass::ALL
+            .iter()
+            .filter_map(|pass| pass.params(text.font, text.size, &text.effects, text.color));
+
+        // The effect offsets stay in screen space
+        for (offset, color, params) in passes {
+            for glyph in text.glyphs {
+                let texture = match text.font.page_texture(text.size, glyph.page) {
+                    Some(texture) => texture.clone(),
+                    None => continue,
+                };
                 self.draw_vertices(RenderVertices {
-                    texture: Some(texture.clone()),
-                    vertices,
-                    texture_coords: texture_coords.clone(),
-                    color: _text.color,
+                    texture_coords: glyph.texture_coords(&texture),
+                    texture: Some(texture),
+                    normal_texture: None,
+                    vertices: glyph.vertices.map(|vertex| vertex + offset.extend(0.0)),
+                    color,
+                    lit: false,
+                    wrap: false,
+                    sdf: params,
                 })
-            },
-        )
+            }
+        }
+    }
+
+    pub fn draw_rich_text(&mut self, text: RenderRichText) {
+        let transform =
+            glam::Affine2::from_scale_angle_translation(text.scale, text.rotate, text.position);
+        for pass in TextPass::ALL {
+            let glyphs = text.layout.lines.iter().flat_map(|line| &line.glyphs);
+            for (index, glyph) in glyphs.enumerate() {
+                let span = &text.spans[glyph.span];
+                let animation = span.animation.map_or(glam::Vec2::ZERO, |animation| {
+                    animation.offset(text.time, index, span.size)
+                });
+
+                match &span.content {
+                    RichContent::Text(_) => {
+                        self.draw_rich_glyph(pass, span, glyph, transform, animation, &text.effects)
+                    }
+                    // Inline images are only drawn with the text
+                    RichContent::Image(image) if matches!(pass, TextPass::Fill) => {
+                        self.draw_inline_image(image, glyph, transform, animation, span.size)
+                    }
+                    RichContent::Image(_) => {}
+                }
+            }
+        }
+    }
+
+    fn draw_rich_glyph(
+        &mut self,
+        pass: TextPass,
+        span: &RichSpan,
+        glyph: &PositionedGlyph,
+        transform: glam::Affine2,
+        animation: glam::Vec2,
+        effects: &TextEffects,
+    ) {
+        let (offset, color, params) = match pass.params(&span.font, span.size, effects, span.color)
+        {
+            Some(params) => params,
+            None => return,
+        };
+        let (texture, vertices, texture_coords) =
+            match span.font.glyph_vertices(glyph, animation, span.size) {
+                Some(quad) => quad,
+                None => return,
+            };
+
+        // Fake bold, the glyph is drawn again a little to the right
+        let copies = if span.bold { 2 } else { 1 };
+        let bold_offset = (span.size as f32 / 24.0).max(1.0);
+        for copy in 0..copies {
+            let shift = glam::vec2(copy as f32 * bold_offset, 0.0);
+            self.draw_vertices(RenderVertices {
+                texture: Some(texture.clone()),
+                normal_texture: None,
+                // The effect offsets stay in screen space
+                vertices: vertices.map(|vertex| {
+                    (transform.transform_point2(vertex.truncate() + shift) + offset).extend(0.0)
+                }),
+                texture_coords,
+                color,
+                lit: false,
+                wrap: false,
+                sdf: params,
+            });
+        }
+    }
+
+    // The image sits on the baseline and is as tall as the size of its span
+    fn draw_inline_image(
+        &mut self,
+        image: &RichImage,
+        glyph: &PositionedGlyph,
+        transform: glam::Affine2,
+        animation: glam::Vec2,
+        size: u32,
+    ) {
+        let (w, h) = (glyph.advance, size as f32);
+        let x = animation.x + glyph.position.x;
+        let y = animation.y + glyph.position.y - h;
+        let vertices = [
+            glam::vec2(x + w, y),
+            glam::vec2(x, y),
+            glam::vec2(x, y + h),
+            glam::vec2(x + w, y + h),
+        ];
+        self.draw_vertices(RenderVertices {
+            texture: Some(image.texture.clone()),
+            normal_texture: None,
+            vertices: vertices.map(|vertex| transform.transform_point2(vertex).extend(0.0)),
+            texture_coords: image.texture_coords,
+            color: glam::Vec4::ONE,
+            lit: false,
+            wrap: false,
+            sdf: None,
+        });
     }
 
     pub fn draw_vertices(&mut self, vertices: RenderVertices) {
         self.render2d_pipeline.draw_vertices(vertices);
     }
+
+    // Large runs of instances sharing their textures are drawn with a single draw call
+    pub fn draw_instance(&mut self, instance: RenderInstance) {
+        self.render2d_pipeline.draw_instance(instance);
+    }
 }
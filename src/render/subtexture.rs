@@ -2,12 +2,24 @@ use crate::shapes::rectangle::Rect;
 
 use super::texture::Texture;
 
+// Transparent borders removed from a frame by the packer
+#[derive(Debug, Clone, Copy)]
+pub struct FrameTrim {
+    // Position of the region in the untrimmed frame
+    pub offset: glam::Vec2,
+    pub source_size: glam::Vec2,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct SubTexture {
     pub region: Rect,
     pub texture_size: glam::Vec2,
     pub flip_x: bool,
     pub flip_y: bool,
+    // The frame is packed turned 90 degrees clockwise, the region is the rect it takes
+    // in the texture
+    pub rotated: bool,
+    pub trim: Option<FrameTrim>,
     pub(crate) texture_coords: Option<[glam::Vec2; 4]>,
 }
 
@@ -18,10 +30,47 @@ impl SubTexture {
             texture_size: glam::Vec2::ZERO,
             flip_x: false,
             flip_y: false,
+            rotated: false,
+            trim: None,
             texture_coords: None,
         }
     }
 
+    // Size of the frame as drawn, before trimming
+    pub fn frame_size(&self) -> glam::Vec2 {
+        match self.trim {
+            Some(trim) => trim.source_size,
+            None => self.content_size(),
+        }
+    }
+
+    // Size of the region once turned back upright
+    pub fn content_size(&self) -> glam::Vec2 {
+        let size = glam::vec2(self.region.width, self.region.height);
+        if self.rotated {
+            glam::vec2(size.y, size.x)
+        } else {
+            size
+        }
+    }
+
+    // Offset and size of the region relative to the frame size, flips mirror the offset
+    pub(crate) fn content_rect(&self) -> (glam::Vec2, glam::Vec2) {
+        let trim = match self.trim {
+            Some(trim) if trim.source_size.x > 0.0 && trim.source_size.y > 0.0 => trim,
+            _ => return (glam::Vec2::ZERO, glam::Vec2::ONE),
+        };
+        let size = self.content_size() / trim.source_size;
+        let mut offset = trim.offset / trim.source_size;
+        if self.flip_x {
+            offset.x = 1.0 - offset.x - size.x;
+        }
+        if self.flip_y {
+            offset.y = 1.0 - offset.y - size.y;
+        }
+        (offset, size)
+    }
+
     pub fn new_with_texture_size(region: Rect, width: f32, height: f32) -> SubTexture {
         let mut sub_texture = SubTexture::new(region);
         sub_texture.texture_size.x = width;
@@ -55,6 +104,8 @@ impl SubTexture {
         // 1 - TOP LEFT
         // 2 - BOTTOM LEFT
         // 3 - BOTTOM RIGHT
+        // rotated, the frame top left is the region top right
+        // 0 - 3, 1 - 0, 2 - 1, 3 - 2
         // flip x
         // 0 - 1
         // 2 - 3
@@ -62,6 +113,15 @@ impl SubTexture {
         // 1 - 2
         // 0 - 3
 
+        if self.rotated {
+            texture_coords = [
+                texture_coords[3],
+                texture_coords[0],
+                texture_coords[1],
+                texture_coords[2],
+            ];
+        }
+
         if self.flip_x {
             let tmp = texture_coords[0];
             texture_coords[0] = texture_coords[1];
@@ -1,90 +1,239 @@
 use std::num::NonZeroU32;
 
-use image::DynamicImage;
+use image::{DynamicImage, ImageBuffer, Pixel};
+
+use crate::error::{BreakoutError, BreakoutResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFilter {
+    // Keeps hard pixel edges, for pixel art
+    Nearest,
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureAddressMode {
+    ClampToEdge,
+    Repeat,
+    MirrorRepeat,
+}
+
+impl From<TextureAddressMode> for wgpu::AddressMode {
+    fn from(address_mode: TextureAddressMode) -> Self {
+        match address_mode {
+            TextureAddressMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+            TextureAddressMode::Repeat => wgpu::AddressMode::Repeat,
+            TextureAddressMode::MirrorRepeat => wgpu::AddressMode::MirrorRepeat,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TextureOptions {
+    pub filter: TextureFilter,
+    pub address_mode: TextureAddressMode,
+    pub mipmaps: bool,
+    // One 8 bit channel instead of RGBA, for masks and glyphs
+    pub single_channel: bool,
+    // Stored without the sRGB decoding, for data like normal maps that holds vectors, not colors
+    pub linear_color: bool,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self {
+            filter: TextureFilter::Nearest,
+            address_mode: TextureAddressMode::ClampToEdge,
+            mipmaps: false,
+            single_channel: false,
+            linear_color: false,
+        }
+    }
+}
+
+impl TextureOptions {
+    // Smooth filtering with mipmaps, for high resolution art that gets scaled down
+    pub fn linear() -> TextureOptions {
+        TextureOptions {
+            filter: TextureFilter::Linear,
+            mipmaps: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn mask() -> TextureOptions {
+        TextureOptions {
+            filter: TextureFilter::Linear,
+            single_channel: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_address_mode(mut self, address_mode: TextureAddressMode) -> Self {
+        self.address_mode = address_mode;
+        self
+    }
+
+    pub fn with_linear_color(mut self, linear_color: bool) -> Self {
+        self.linear_color = linear_color;
+        self
+    }
+
+    fn create_sampler(
+        &self,
+        device: &wgpu::Device,
+        address_mode: wgpu::AddressMode,
+    ) -> wgpu::Sampler {
+        let filter = match self.filter {
+            TextureFilter::Nearest => wgpu::FilterMode::Nearest,
+            TextureFilter::Linear => wgpu::FilterMode::Linear,
+        };
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: filter,
+            ..Default::default()
+        })
+    }
+}
 
 pub struct Texture {
     pub id: Option<i32>,
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
+    // Same filtering as sampler but repeating, used by tiled sprites
+    pub wrap_sampler: wgpu::Sampler,
     pub width: u32,
     pub height: u32,
+    // Stored as R8, drawn as white with the channel as alpha
+    pub single_channel: bool,
 }
 
 impl Texture {
-    pub fn from_file(path: &str, device: &wgpu::Device, queue: &wgpu::Queue) -> Texture {
-        let bytes = std::fs::read(path).unwrap();
-        Texture::from_byte(&bytes, device, queue)
+    pub fn from_file(
+        path: &str,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> BreakoutResult<Texture> {
+        Texture::from_file_with_options(path, TextureOptions::default(), device, queue)
+    }
+
+    pub fn from_file_with_options(
+        path: &str,
+        options: TextureOptions,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> BreakoutResult<Texture> {
+        let bytes = std::fs::read(path).map_err(BreakoutError::IOError)?;
+        Texture::from_byte_with_options(&bytes, options, device, queue)
     }
 
     pub fn from_dynamic_image(
         image: DynamicImage,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-    ) -> Texture {
-        let (width, height) = image.dimensions();
-        let data = match image {
-            image::DynamicImage::ImageRgba8(i) => i.into_raw(),
-            image::DynamicImage::ImageRgb8(i) => {
-                let i = DynamicImage::ImageRgb8(i).into_rgba8();
-                i.into_raw()
-            }
-            _ => panic!("ColorType not supportes"),
-        };
+    ) -> BreakoutResult<Texture> {
+        Texture::from_dynamic_image_with_options(image, TextureOptions::default(), device, queue)
+    }
 
+    // Any color type is converted to 8 bit RGBA, or to a single 8 bit channel when asked for
+    pub fn from_dynamic_image_with_options(
+        image: DynamicImage,
+        options: TextureOptions,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> BreakoutResult<Texture> {
         use image::GenericImageView;
 
+        let (width, height) = image.dimensions();
+        if width == 0 || height == 0 {
+            return Err(BreakoutError::RenderError("Texture size can't be zero"));
+        }
+
         let texture_size = wgpu::Extent3d {
             width,
             height,
             depth_or_array_layers: 1,
         };
+        let mip_level_count = if options.mipmaps {
+            32 - width.max(height).leading_zeros()
+        } else {
+            1
+        };
+        let format = if options.single_channel {
+            wgpu::TextureFormat::R8Unorm
+        } else if options.linear_color {
+            wgpu::TextureFormat::Rgba8Unorm
+        } else {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        };
 
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             size: texture_size,
-            mip_level_count: 1,
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            format,
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
             label: Some("texture"),
         });
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
+        if options.single_channel {
+            // Masks and glyphs keep their coverage in the alpha channel when they have one
+            let mask = match image {
+                DynamicImage::ImageLumaA8(_)
+                | DynamicImage::ImageLumaA16(_)
+                | DynamicImage::ImageRgba8(_)
+                | DynamicImage::ImageRgba16(_)
+                | DynamicImage::ImageBgra8(_) => {
+                    let rgba = image.into_rgba8();
+                    image::GrayImage::from_fn(width, height, |x, y| {
+                        image::Luma([rgba.get_pixel(x, y)[3]])
+                    })
+                }
+                _ => image.into_luma8(),
+            };
+            write_mip_levels(queue, &texture, &mask, mip_level_count);
+        } else {
+            write_mip_levels(queue, &texture, &image.into_rgba8(), mip_level_count);
+        }
 
-        queue.write_texture(
-            texture.as_image_copy(),
-            &data,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: std::num::NonZeroU32::new(4 * width),
-                rows_per_image: std::num::NonZeroU32::new(height),
-            },
-            texture_size,
-        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = options.create_sampler(device, options.address_mode.into());
+        let wrap_sampler = options.create_sampler(device, wgpu::AddressMode::Repeat);
 
-        Texture {
+        Ok(Texture {
             id: None,
             texture,
             view,
             sampler,
+            wrap_sampler,
             width,
             height,
-        }
+            single_channel: options.single_channel,
+        })
     }
 
-    pub fn from_byte(bytes: &[u8], device: &wgpu::Device, queue: &wgpu::Queue) -> Texture {
-        let image = image::load_from_memory(bytes).unwrap();
-        Texture::from_dynamic_image(image, device, queue)
+    pub fn from_byte(
+        bytes: &[u8],
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> BreakoutResult<Texture> {
+        Texture::from_byte_with_options(bytes, TextureOptions::default(), device, queue)
+    }
+
+    pub fn from_byte_with_options(
+        bytes: &[u8],
+        options: TextureOptions,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> BreakoutResult<Texture> {
+        let image = image::load_from_memory(bytes).map_err(BreakoutError::ImageError)?;
+        Texture::from_dynamic_image_with_options(image, options, device, queue)
     }
 
     pub fn from_color(color: [u8; 4], device: &wgpu::Device, queue: &wgpu::Queue) -> Texture {
@@ -102,6 +251,12 @@ impl Texture {
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+        let wrap_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            ..Default::default()
+        });
 
         queue.write_texture(
             texture.as_image_copy(),
@@ -119,8 +274,10 @@ impl Texture {
             texture,
             view,
             sampler,
+            wrap_sampler,
             width: texture_size.width,
             height: texture_size.height,
+            single_channel: false,
         }
     }
 
@@ -128,3 +285,49 @@ impl Texture {
         glam::uvec2(self.width, self.height)
     }
 }
+
+// Mip levels are downscaled on the CPU from the full size image
+fn write_mip_levels<P>(
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    image: &ImageBuffer<P, Vec<u8>>,
+    mip_level_count: u32,
+) where
+    P: Pixel<Subpixel = u8> + 'static,
+{
+    let (width, height) = image.dimensions();
+    for mip_level in 0..mip_level_count {
+        let level_width = (width >> mip_level).max(1);
+        let level_height = (height >> mip_level).max(1);
+        let level = if mip_level == 0 {
+            image.clone()
+        } else {
+            image::imageops::resize(
+                image,
+                level_width,
+                level_height,
+                image::imageops::FilterType::Triangle,
+            )
+        };
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &level,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(P::CHANNEL_COUNT as u32 * level_width),
+                rows_per_image: NonZeroU32::new(level_height),
+            },
+            wgpu::Extent3d {
+                width: level_width,
+                height: level_height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
@@ -1,18 +1,50 @@
+pub const VERTEX_FLAG_LIT: u32 = 1;
+pub const VERTEX_FLAG_WRAP: u32 = 2;
+pub const VERTEX_FLAG_SINGLE_CHANNEL: u32 = 4;
+// The texture holds a distance field, the alpha is computed from the sdf params
+pub const VERTEX_FLAG_SDF: u32 = 8;
+pub const NO_NORMAL_MAP: u32 = u32::MAX;
+
 #[repr(C, align(16))]
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug)]
 pub struct Vertex {
     pub position: glam::Vec3,
     pub color: glam::Vec4,
     pub texture_coords: glam::Vec2,
     pub tex_index: u32,
+    pub flags: u32,
+    pub normal_index: u32,
+    // Threshold and softness of distance field glyphs
+    pub sdf: glam::Vec2,
+}
+
+impl Default for Vertex {
+    fn default() -> Self {
+        Self {
+            position: glam::Vec3::ZERO,
+            color: glam::Vec4::ZERO,
+            texture_coords: glam::Vec2::ZERO,
+            tex_index: 0,
+            flags: 0,
+            normal_index: NO_NORMAL_MAP,
+            sdf: glam::vec2(0.5, 0.0),
+        }
+    }
 }
 
 unsafe impl bytemuck::Pod for Vertex {}
 unsafe impl bytemuck::Zeroable for Vertex {}
 
 impl Vertex {
-    const ATTRIBS: [wgpu::VertexAttribute; 4] =
-        wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4, 2 => Float32x2, 3 => Uint32];
+    const ATTRIBS: [wgpu::VertexAttribute; 7] = wgpu::vertex_attr_array![
+        0 => Float32x4,
+        1 => Float32x4,
+        2 => Float32x2,
+        3 => Uint32,
+        4 => Uint32,
+        5 => Uint32,
+        6 => Float32x2
+    ];
 
     pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
         wgpu::VertexBufferLayout {
@@ -23,6 +55,61 @@ impl Vertex {
     }
 }
 
+// Per instance data of the instanced pipeline, the corners of the unit quad are
+// generated in the vertex shader
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Instance {
+    pub x_axis: [f32; 2],
+    pub y_axis: [f32; 2],
+    pub translation: [f32; 2],
+    // Texture coords of the top left corner and the size of the region
+    pub uv_offset: [f32; 2],
+    pub uv_scale: [f32; 2],
+    pub color: [f32; 4],
+    pub tex_index: u32,
+    pub flags: u32,
+    pub normal_index: u32,
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self {
+            x_axis: [1.0, 0.0],
+            y_axis: [0.0, 1.0],
+            translation: [0.0, 0.0],
+            uv_offset: [0.0, 0.0],
+            uv_scale: [1.0, 1.0],
+            color: [0.0; 4],
+            tex_index: 0,
+            flags: 0,
+            normal_index: NO_NORMAL_MAP,
+        }
+    }
+}
+
+impl Instance {
+    const ATTRIBS: [wgpu::VertexAttribute; 9] = wgpu::vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x2,
+        2 => Float32x2,
+        3 => Float32x2,
+        4 => Float32x2,
+        5 => Float32x4,
+        6 => Uint32,
+        7 => Uint32,
+        8 => Uint32
+    ];
+
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Instance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Instance::ATTRIBS,
+        }
+    }
+}
+
 #[rustfmt::skip] 
 // pub const VERTICES: &[Vertex] = &[
 //     Vertex {
@@ -88,6 +88,7 @@ impl Tiled {
         Ok(())
     }
 
+    #[deprecated(note = "sprites are culled against the camera view when drawn")]
     pub fn update(&self, context: &mut GameContext) {
         let camera_rect = context.get_camera_rect();
         let world = &mut context.get_world();